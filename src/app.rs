@@ -1,47 +1,271 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::error::Error;
+use std::fs;
+use std::io;
 use std::ops::RangeInclusive;
+use std::path::Path;
 
-use eframe::egui::{self, TextEdit, Label, Sense, DragValue, RichText};
-use egui_plot::{Line, Plot, PlotPoints, GridMark};
+use eframe::egui::{self, TextEdit, Label, Sense, DragValue, RichText, TextFormat};
+use eframe::egui::text::LayoutJob;
+use egui_plot::{AxisHints, Bar, BarChart, HPlacement, Legend, Line, HLine, LineStyle, Plot, PlotPoint, PlotPoints, GridMark};
 use ecolor::Color32;
-use time::{Date, OffsetDateTime, format_description};
+use egui_commonmark::{CommonMarkCache, CommonMarkViewer};
+use time::{Date, Month, OffsetDateTime, Weekday, format_description};
+use time::format_description::OwnedFormatItem;
 use serde::{Deserialize, Serialize};
 
+use crate::crypto;
+use crate::raster;
+
 #[derive(Clone, Serialize, Deserialize)]
 pub struct Entry {
     pub content: String,
-    pub weight_kg: f32,
-    pub waist_cm: f32,
     pub date: Date,
 
+    #[serde(default)]
+    pub mood: Option<u8>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub pinned: bool,
+    #[serde(default)]
+    pub image_paths: Vec<String>,
+    #[serde(default)]
+    pub workouts: Vec<String>,
+    // Every numeric reading, built in or custom, keyed by metric name
+    #[serde(default)]
+    pub values: HashMap<String, f32>,
+    #[serde(default)]
+    pub locked: bool,
+
     #[serde(default)]
     pub edit: bool,
+    #[serde(skip)]
+    pub tags_text: String,
+    #[serde(skip)]
+    pub workouts_text: String,
 }
 
-#[derive(serde::Serialize, serde::Deserialize)]
+impl Entry {
+    pub fn new(date: Date) -> Self {
+        Entry {
+            content: String::new(),
+            date,
+            mood: None,
+            tags: vec![],
+            pinned: false,
+            image_paths: vec![],
+            workouts: vec![],
+            values: HashMap::new(),
+            locked: false,
+            edit: false,
+            tags_text: String::new(),
+            workouts_text: String::new(),
+        }
+    }
+
+    pub fn has_data(&self) -> bool {
+        self.content.len() > 0
+            || self.mood.is_some()
+            || self.image_paths.len() > 0
+            || self.workouts.len() > 0
+            || self.values.len() > 0
+    }
+
+    pub fn value(&self, name: &str) -> Option<f32> {
+        self.values.get(name).copied()
+    }
+
+    // Zero clears a reading, the same as the fixed fields used to
+    pub fn set_value(&mut self, name: &str, value: f32) {
+        if value == 0.0 {
+            self.values.remove(name);
+        } else {
+            self.values.insert(name.to_string(), value);
+        }
+    }
+
+    pub fn weight_kg(&self) -> f32 {
+        self.value(WEIGHT).unwrap_or(0.0)
+    }
+
+    pub fn waist_cm(&self) -> f32 {
+        self.value(WAIST).unwrap_or(0.0)
+    }
+
+    pub fn sleep_hours(&self) -> f32 {
+        self.value(SLEEP).unwrap_or(0.0)
+    }
+
+    pub fn resting_hr(&self) -> Option<u16> {
+        self.value(RESTING_HR).map(|resting_hr| resting_hr as u16)
+    }
+
+    pub fn calories(&self) -> Option<u32> {
+        self.value(CALORIES).map(|calories| calories as u32)
+    }
+
+    pub fn body_fat_pct(&self) -> Option<f32> {
+        self.value(BODY_FAT)
+    }
+
+    // Private entries stay hidden everywhere until clicked to reveal
+    pub fn is_hidden(&self, revealed: &HashSet<Date>) -> bool {
+        self.locked && !revealed.contains(&self.date)
+    }
+
+    pub fn to_plain_text(&self, units: Units, date_format: &OwnedFormatItem, revealed: &HashSet<Date>, metrics: &[MetricDef]) -> String {
+        let mut text = format!("{}\n", self.date.format(date_format).unwrap());
+
+        if self.weight_kg() != 0.0 {
+            text.push_str(&format!("Weight: {:.1}{}\n", self.weight_kg() * units.weight_factor(), units.weight_suffix()));
+        }
+        if self.waist_cm() != 0.0 {
+            text.push_str(&format!("Waist: {:.1}{}\n", self.waist_cm() * units.length_factor(), units.length_suffix()));
+        }
+        if let Some(body_fat_pct) = self.body_fat_pct() {
+            text.push_str(&format!("Body fat: {:.1} %\n", body_fat_pct));
+        }
+        for metric in metrics {
+            if let Some(value) = self.value(&metric.name) {
+                text.push_str(&format!("{}: {:.1} {}\n", metric.name, value, metric.unit));
+            }
+        }
+
+        if self.is_hidden(revealed) {
+            text.push_str("\n[locked]\n");
+        } else if self.content.len() > 0 {
+            text.push_str("\n");
+            text.push_str(&self.content);
+            text.push_str("\n");
+        }
+
+        text
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+pub enum Recurrence {
+    Daily,
+    Weekly,
+    Weekdays,
+}
+
+impl Recurrence {
+    fn label(recurrence: Option<Recurrence>) -> &'static str {
+        match recurrence {
+            None => "Once",
+            Some(Recurrence::Daily) => "Daily",
+            Some(Recurrence::Weekly) => "Weekly",
+            Some(Recurrence::Weekdays) => "Weekdays",
+        }
+    }
+
+    // Whether a task done on `prev_date` is due again by `new_date`
+    fn is_due(&self, prev_date: Date, new_date: Date) -> bool {
+        let prev_julian = prev_date.to_julian_day();
+        let new_julian = new_date.to_julian_day();
+
+        if new_julian <= prev_julian {
+            return false;
+        }
+
+        match self {
+            Recurrence::Daily => true,
+            Recurrence::Weekly => {
+                // Due once a new week has started
+                let week_start_julian = new_julian - new_date.weekday().number_days_from_monday() as i32;
+                week_start_julian > prev_julian
+            },
+            Recurrence::Weekdays => {
+                // Due if any weekday passed since it was last done
+                (prev_julian + 1..=new_julian.min(prev_julian + 7)).any(|julian| {
+                    Date::from_julian_day(julian).unwrap().weekday().number_days_from_monday() < 5
+                })
+            },
+        }
+    }
+}
+
+#[derive(Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, serde::Serialize, serde::Deserialize)]
+pub enum Priority {
+    Low,
+    #[default]
+    Medium,
+    High,
+}
+
+impl Priority {
+    fn label(&self) -> &'static str {
+        match self {
+            Priority::Low => "Low",
+            Priority::Medium => "Med",
+            Priority::High => "High",
+        }
+    }
+
+    fn next(&self) -> Self {
+        match self {
+            Priority::Low => Priority::Medium,
+            Priority::Medium => Priority::High,
+            Priority::High => Priority::Low,
+        }
+    }
+
+    fn color(&self) -> Option<Color32> {
+        match self {
+            Priority::Low => Some(Color32::GRAY),
+            Priority::Medium => None,
+            Priority::High => Some(Color32::RED),
+        }
+    }
+}
+
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
 pub struct Task {
     text: String,
     done: bool,
     edit: bool,
-    delete: bool,
+
+    #[serde(default)]
+    recurrence: Option<Recurrence>,
+    #[serde(default)]
+    priority: Priority,
+    #[serde(default)]
+    note: String,
+    #[serde(default)]
+    subtasks: Vec<(String, bool)>,
 }
 
 impl Task {
+    // A parent only counts as finished once every step is checked too
+    fn is_complete(&self) -> bool {
+        self.done && self.subtasks.iter().all(|(_, done)| *done)
+    }
+
     fn default() -> Self {
         Task {
             text: String::from("New task"),
             done: false,
             edit: false,
-            delete: false,
+            recurrence: None,
+            priority: Priority::Medium,
+            note: String::new(),
+            subtasks: vec![],
         }
     }
 }
 
-#[derive(serde::Serialize, serde::Deserialize)]
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
 pub struct Section {
     title: String,
     tasks: Vec<Task>,
     edit: bool,
-    delete: bool,
+
+    #[serde(default)]
+    collapsed: bool,
+    #[serde(default)]
+    due: Option<Date>,
 }
 
 impl Section {
@@ -50,13 +274,184 @@ impl Section {
             title: String::from("New Section"),
             tasks: vec![Task::default()],
             edit: true,
-            delete: false,
+            collapsed: false,
+            due: None,
         }
     }
 
     fn add_task(&mut self, task: &str, edit: bool) {
-        self.tasks.push(Task {text: task.to_string(), done: false, edit, delete: false});
+        self.tasks.push(Task {text: task.to_string(), done: false, edit, recurrence: None, priority: Priority::Medium, note: String::new(), subtasks: vec![]});
+    }
+}
+
+pub struct WeeklyReview {
+    pub start: Date,
+    pub end: Date,
+    pub days: Vec<(Date, String)>,
+    pub average_weight: Option<f32>,
+    pub min_weight: Option<f32>,
+    pub max_weight: Option<f32>,
+    pub tasks_done: usize,
+    pub tasks_open: usize,
+}
+
+// A user defined measurement, its values live in Entry::values under its name
+#[derive(Clone, Serialize, Deserialize)]
+pub struct MetricDef {
+    pub name: String,
+    pub unit: String,
+    pub color: [u8; 3],
+}
+
+#[derive(Clone, Copy, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub enum Units {
+    #[default]
+    Metric,
+    Imperial,
+}
+
+impl Units {
+    // Weights and lengths are always stored in kg and cm, these convert for display
+    fn weight_factor(&self) -> f32 {
+        match self {
+            Units::Metric => 1.0,
+            Units::Imperial => 2.204_623,
+        }
     }
+
+    fn length_factor(&self) -> f32 {
+        match self {
+            Units::Metric => 1.0,
+            Units::Imperial => 1.0 / 2.54,
+        }
+    }
+
+    fn weight_suffix(&self) -> &'static str {
+        match self {
+            Units::Metric => " kg",
+            Units::Imperial => " lb",
+        }
+    }
+
+    fn length_suffix(&self) -> &'static str {
+        match self {
+            Units::Metric => " cm",
+            Units::Imperial => " in",
+        }
+    }
+}
+
+pub struct MeasurementStats {
+    pub min: f32,
+    pub max: f32,
+    pub average: f32,
+}
+
+impl MeasurementStats {
+    fn from_values(values: &[f32]) -> Option<Self> {
+        if values.len() == 0 {
+            return None;
+        }
+
+        Some(MeasurementStats {
+            min: values.iter().cloned().fold(f32::INFINITY, f32::min),
+            max: values.iter().cloned().fold(f32::NEG_INFINITY, f32::max),
+            average: values.iter().sum::<f32>() / values.len() as f32,
+        })
+    }
+}
+
+pub struct EntryStats {
+    pub weight_kg: Option<MeasurementStats>,
+    pub waist_cm: Option<MeasurementStats>,
+    pub days_journaled: usize,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum StatsRange {
+    Week,
+    Month,
+}
+
+#[derive(Clone, Copy)]
+pub enum PendingDelete {
+    Entry(Date),
+    Section(usize),
+    Task(usize, usize),
+    CompletedTasks { tasks: usize, sections: usize },
+}
+
+impl PendingDelete {
+    fn question(&self) -> String {
+        match self {
+            PendingDelete::Entry(_) => String::from("Delete this entry?"),
+            PendingDelete::Section(_) => String::from("Delete this section and all its tasks?"),
+            PendingDelete::Task(_, _) => String::from("Delete this task?"),
+            PendingDelete::CompletedTasks { tasks, sections } => {
+                format!("Clear {} completed tasks and {} empty sections?", tasks, sections)
+            },
+        }
+    }
+}
+
+const MAX_UNDO: usize = 20;
+
+const TOAST_SECONDS: f64 = 4.0;
+
+// Everything listed by the ? overlay, add new shortcuts here too
+const SHORTCUTS: &[(&str, &str)] = &[
+    ("?  /  F1", "Show or hide this help"),
+    ("T", "Go to today"),
+    ("E", "Write today's entry"),
+    ("Z  /  F11", "Distraction-free writing, Esc to leave"),
+    ("Ctrl+Z", "Undo"),
+    ("Ctrl+Plus  /  Ctrl+Minus", "Make the interface bigger or smaller"),
+    ("Left  /  Right", "Show the charts by day or by week"),
+    ("Up  /  Down", "Select an entry"),
+    ("Enter", "Edit the selected entry, or finish editing"),
+    ("N  /  Page Up", "Next written entry"),
+    ("P  /  Page Down", "Previous written entry"),
+    ("R", "Only show entries with text"),
+    ("C", "Clear completed tasks"),
+    ("S", "Sort tasks by priority"),
+    ("Esc", "Finish editing"),
+    ("Tab  /  Shift+Tab", "Cycle the fields of the entry being edited"),
+    ("Ctrl+T", "Insert the time in the entry being edited"),
+    ("Alt+Up  /  Alt+Down", "Move the task being edited"),
+    ("Alt+Left  /  Alt+Right", "Move the section being edited"),
+];
+
+const CLICKS: &[(&str, &str)] = &[
+    ("Entry date or text", "Edit the entry"),
+    ("Section title or task", "Edit it"),
+    ("Space below the tasks", "Add a task or a section"),
+    ("Calendar day", "Go to that day"),
+    ("Weight chart", "Go to the nearest entry"),
+    ("Tag", "Only show entries with that tag"),
+    ("Photo", "Show it full size"),
+    ("🔒", "Reading mode, nothing can be edited"),
+];
+
+// Bump together with a new step in migrate whenever the saved layout changes
+const SCHEMA_VERSION: u32 = 2;
+
+// Names of the built in readings in Entry::values, custom metrics can't use them
+const WEIGHT: &str = "Weight";
+const WAIST: &str = "Waist";
+const SLEEP: &str = "Sleep";
+const RESTING_HR: &str = "Resting HR";
+const CALORIES: &str = "Calories";
+const BODY_FAT: &str = "Body fat";
+const BUILT_IN_METRICS: [&str; 6] = [WEIGHT, WAIST, SLEEP, RESTING_HR, CALORIES, BODY_FAT];
+
+// Anything outside these is a typo and would wreck the chart scales
+const MAX_WEIGHT_KG: f32 = 500.0;
+const MAX_WAIST_CM: f32 = 300.0;
+
+pub struct Snapshot {
+    entries: Vec<Entry>,
+    sections: Vec<Section>,
+    archived_tasks: Vec<(Date, String)>,
 }
 
 #[derive(serde::Serialize, serde::Deserialize)]
@@ -71,9 +466,52 @@ pub enum Zoom {
     Week,
 }
 
+#[derive(Clone, Copy, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub enum Theme {
+    #[default]
+    Dark,
+    Light,
+    System,
+}
+
+impl Theme {
+    fn preference(&self) -> egui::ThemePreference {
+        match self {
+            Theme::Dark => egui::ThemePreference::Dark,
+            Theme::Light => egui::ThemePreference::Light,
+            Theme::System => egui::ThemePreference::System,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub enum ExtraChart {
+    #[default]
+    Sleep,
+    HeartRate,
+    Calories,
+    BodyFat,
+    WaistToHeight,
+}
+
+impl ExtraChart {
+    fn label(&self) -> &'static str {
+        match self {
+            ExtraChart::Sleep => "Sleep",
+            ExtraChart::HeartRate => "Resting HR",
+            ExtraChart::Calories => "Calories",
+            ExtraChart::BodyFat => "Body fat",
+            ExtraChart::WaistToHeight => "Waist to height",
+        }
+    }
+}
+
 
 #[derive(serde::Serialize, serde::Deserialize)]
 pub struct MyApp {
+    #[serde(default)]
+    pub schema_version: u32,
+
     pub sections: Vec<Section>,
     pub entries: Vec<Entry>,
     pub curr_date: Date,
@@ -84,35 +522,450 @@ pub struct MyApp {
     pub scale_factor: f32,
     pub redux_mode: bool,
     pub path_to_file: String,
+
+    #[serde(default = "default_trend_window")]
+    pub trend_window: usize,
+    #[serde(default)]
+    pub goal_weight_kg: Option<f32>,
+    #[serde(default)]
+    pub calorie_goal: Option<u32>,
+    #[serde(default = "default_backup_count")]
+    pub backup_count: usize,
+    #[serde(default)]
+    pub height_cm: f32,
+    #[serde(default)]
+    pub show_bmi: bool,
+    #[serde(default)]
+    pub archived_tasks: Vec<(Date, String)>,
+    #[serde(default)]
+    pub units: Units,
+    #[serde(default = "default_today")]
+    pub today: Date,
+    #[serde(default)]
+    pub encrypted: bool,
+    #[serde(default = "default_autosave_secs")]
+    pub autosave_secs: u64,
+    #[serde(default = "default_date_format")]
+    pub date_format: String,
+    #[serde(default)]
+    pub overlay_charts: bool,
+    #[serde(default)]
+    pub weekly_average: bool,
+    #[serde(default = "default_render_markdown")]
+    pub render_markdown: bool,
+    #[serde(default)]
+    pub extra_chart: ExtraChart,
+    #[serde(default)]
+    pub theme: Theme,
+    #[serde(default = "default_weight_color")]
+    pub weight_color: [u8; 3],
+    #[serde(default = "default_waist_color")]
+    pub waist_color: [u8; 3],
+    #[serde(default = "default_reminder_days")]
+    pub reminder_days: i64,
+    #[serde(default = "default_chart_export_width")]
+    pub chart_export_width: u32,
+    #[serde(default)]
+    pub window_size: Option<[f32; 2]>,
+    #[serde(default)]
+    pub window_pos: Option<[f32; 2]>,
+    #[serde(default = "default_profiles")]
+    pub profiles: Vec<String>,
+    #[serde(default = "default_active_profile")]
+    pub active_profile: String,
+    // Diary files of the profiles that aren't active, custom locations included
+    #[serde(default)]
+    pub profile_paths: HashMap<String, String>,
+    #[serde(default = "default_content_font_size")]
+    pub content_font_size: f32,
+    #[serde(default = "default_week_start")]
+    pub week_start: Weekday,
+    #[serde(default)]
+    pub entry_template: String,
+    #[serde(default)]
+    pub prompts: Vec<String>,
+    #[serde(default)]
+    pub metrics: Vec<MetricDef>,
+    #[serde(default)]
+    pub reminder_hour: Option<u8>,
+
+    #[serde(skip)]
+    pub show_settings: bool,
+    #[serde(skip)]
+    pub import_path: String,
+    #[serde(skip)]
+    pub import_message: String,
+    #[serde(skip)]
+    pub import_errors: Vec<String>,
+    #[serde(skip)]
+    pub export_path: String,
+    #[serde(skip)]
+    pub export_message: String,
+    #[serde(skip)]
+    pub save_error: Option<String>,
+    #[serde(skip)]
+    pub search_query: String,
+    #[serde(skip)]
+    pub undo_stack: Vec<Snapshot>,
+    #[serde(skip, default = "default_stats_range")]
+    pub stats_range: StatsRange,
+    #[serde(skip)]
+    pub calendar_offset: i32,
+    #[serde(skip)]
+    pub scroll_to_date: Option<Date>,
+    #[serde(skip)]
+    pub tag_filter: Option<String>,
+    #[serde(skip)]
+    pub password: String,
+    #[serde(skip)]
+    pub derived_key: Option<(String, crypto::DerivedKey)>,
+    #[serde(skip)]
+    pub locked: bool,
+    #[serde(skip)]
+    pub unlock_error: Option<String>,
+    #[serde(skip)]
+    pub pending_delete: Option<PendingDelete>,
+    #[serde(skip)]
+    pub new_profile: String,
+    #[serde(skip)]
+    pub month_view: bool,
+    #[serde(skip)]
+    pub show_replace: bool,
+    #[serde(skip)]
+    pub replace_find: String,
+    #[serde(skip)]
+    pub replace_with: String,
+    #[serde(skip)]
+    pub replace_ignore_case: bool,
+    #[serde(skip)]
+    pub replace_message: String,
+    #[serde(skip)]
+    pub replace_count: Option<(String, bool, usize)>,
+    #[serde(skip)]
+    pub reminder_dismissed: bool,
+    #[serde(skip)]
+    pub viewing_image: Option<String>,
+    #[serde(skip)]
+    pub weight_chart_rect: Option<egui::Rect>,
+    #[serde(skip)]
+    pub chart_message: String,
+    #[serde(skip)]
+    pub window_restored: bool,
+    #[serde(skip, default = "default_date_format_items")]
+    pub date_format_items: OwnedFormatItem,
+    #[serde(skip, default = "default_axis_date_format")]
+    pub axis_date_format: OwnedFormatItem,
+    #[serde(skip)]
+    pub date_format_error: Option<String>,
+    #[serde(skip)]
+    pub markdown_cache: CommonMarkCache,
+    #[serde(skip)]
+    pub tag_suggestion: usize,
+    #[serde(skip)]
+    pub quick_weight: Option<f32>,
+    #[serde(skip)]
+    pub selected_entry: Option<Date>,
+    #[serde(skip)]
+    pub copy_target: usize,
+    #[serde(skip)]
+    pub copy_message: String,
+    #[serde(skip)]
+    pub date_edit_error: Option<String>,
+    #[serde(skip)]
+    pub backup_path: String,
+    #[serde(skip)]
+    pub backup_message: String,
+    #[serde(skip)]
+    pub pending_import: Option<String>,
+    #[serde(skip)]
+    pub confirm_plain_export: bool,
+    #[serde(skip)]
+    pub read_only: bool,
+    #[serde(skip)]
+    pub new_metric: String,
+    #[serde(skip)]
+    pub focus_mode: bool,
+    #[serde(skip)]
+    pub show_help: bool,
+    #[serde(skip)]
+    pub weigh_in_due: bool,
+    #[serde(skip)]
+    pub revealed: HashSet<Date>,
+    #[serde(skip)]
+    pub hide_locked: bool,
+    #[serde(skip)]
+    pub pending_private_edit: Option<Date>,
+    #[serde(skip)]
+    pub private_edit_focus: bool,
+    #[serde(skip)]
+    pub show_review: bool,
+    #[serde(skip)]
+    pub toast: Option<(String, f64)>,
+}
+
+fn default_trend_window() -> usize {
+    7
+}
+
+fn default_backup_count() -> usize {
+    5
+}
+
+fn default_autosave_secs() -> u64 {
+    30
+}
+
+fn default_today() -> Date {
+    // The local offset can be unavailable, e.g. with several threads running
+    OffsetDateTime::now_local().unwrap_or_else(|_| OffsetDateTime::now_utc()).date()
+}
+
+fn default_stats_range() -> StatsRange {
+    StatsRange::Week
+}
+
+fn default_date_format() -> String {
+    String::from("[day]-[month]-[year]")
+}
+
+fn default_date_format_items() -> OwnedFormatItem {
+    format_description::parse_owned::<1>(&default_date_format()).unwrap()
+}
+
+fn default_axis_date_format() -> OwnedFormatItem {
+    format_description::parse_owned::<1>("[day]/[month]").unwrap()
+}
+
+// The axis has little room, so it gets the user format without the year
+fn short_date_format(format: &str) -> String {
+    let start = match format.find("[year") {
+        Some(start) => start,
+        None => return format.to_string(),
+    };
+    let end = match format[start..].find(']') {
+        Some(length) => start + length + 1,
+        None => return format.to_string(),
+    };
+
+    let short = format!("{}{}", &format[..start], &format[end..]);
+    let short = short.trim_start_matches(|c| c != '[').trim_end_matches(|c| c != ']');
+
+    if short.is_empty() {
+        format.to_string()
+    } else {
+        short.to_string()
+    }
+}
+
+fn default_render_markdown() -> bool {
+    true
+}
+
+fn default_reminder_days() -> i64 {
+    3
+}
+
+fn default_chart_export_width() -> u32 {
+    1200
+}
+
+fn default_weight_color() -> [u8; 3] {
+    [0, 255, 255]
+}
+
+fn default_waist_color() -> [u8; 3] {
+    [255, 255, 0]
+}
+
+fn default_week_start() -> Weekday {
+    Weekday::Monday
+}
+
+fn default_content_font_size() -> f32 {
+    14.0
+}
+
+fn default_profiles() -> Vec<String> {
+    vec![default_active_profile()]
+}
+
+fn default_active_profile() -> String {
+    String::from("default")
+}
+
+// The default profile keeps the original file name so existing diaries carry over
+fn profile_path(profile: &str) -> String {
+    if profile == default_active_profile() {
+        String::from("diary.json")
+    } else {
+        format!("diary_{}.json", profile)
+    }
 }
 
 impl MyApp {
     fn default() -> Self {
         MyApp {
+            schema_version: SCHEMA_VERSION,
+
             sections: vec![Section::default()],
             entries: vec![],
-            curr_date: OffsetDateTime::now_local().unwrap().date(),
+            curr_date: default_today(),
             mode: Mode::Main,
             zoom: Zoom::Day,
 
             first_time_edit: false,
             scale_factor: 2.0,
             redux_mode: false,
-            path_to_file: String::from("diary.ron"),
+            path_to_file: String::from("diary.json"),
+
+            trend_window: default_trend_window(),
+            goal_weight_kg: None,
+            calorie_goal: None,
+            backup_count: default_backup_count(),
+            height_cm: 0.0,
+            show_bmi: false,
+            archived_tasks: vec![],
+            units: Units::Metric,
+            today: default_today(),
+            encrypted: false,
+            autosave_secs: default_autosave_secs(),
+            date_format: default_date_format(),
+            overlay_charts: false,
+            weekly_average: false,
+            render_markdown: default_render_markdown(),
+            extra_chart: ExtraChart::Sleep,
+            theme: Theme::Dark,
+            weight_color: default_weight_color(),
+            waist_color: default_waist_color(),
+            reminder_days: default_reminder_days(),
+            chart_export_width: default_chart_export_width(),
+            window_size: None,
+            window_pos: None,
+            profiles: default_profiles(),
+            active_profile: default_active_profile(),
+            profile_paths: HashMap::new(),
+            content_font_size: default_content_font_size(),
+            week_start: default_week_start(),
+            entry_template: String::new(),
+            prompts: vec![],
+            metrics: vec![],
+            reminder_hour: None,
+
+            show_settings: false,
+            import_path: String::new(),
+            import_message: String::new(),
+            import_errors: vec![],
+            export_path: String::new(),
+            export_message: String::new(),
+            save_error: None,
+            search_query: String::new(),
+            undo_stack: vec![],
+            stats_range: default_stats_range(),
+            calendar_offset: 0,
+            scroll_to_date: None,
+            tag_filter: None,
+            password: String::new(),
+            derived_key: None,
+            locked: false,
+            unlock_error: None,
+            pending_delete: None,
+            new_profile: String::new(),
+            month_view: false,
+            show_replace: false,
+            replace_find: String::new(),
+            replace_with: String::new(),
+            replace_ignore_case: false,
+            replace_message: String::new(),
+            replace_count: None,
+            reminder_dismissed: false,
+            viewing_image: None,
+            weight_chart_rect: None,
+            chart_message: String::new(),
+            window_restored: false,
+            date_format_items: default_date_format_items(),
+            axis_date_format: default_axis_date_format(),
+            date_format_error: None,
+            markdown_cache: CommonMarkCache::default(),
+            tag_suggestion: 0,
+            quick_weight: None,
+            selected_entry: None,
+            copy_target: 0,
+            copy_message: String::new(),
+            date_edit_error: None,
+            backup_path: String::new(),
+            backup_message: String::new(),
+            pending_import: None,
+            confirm_plain_export: false,
+            read_only: false,
+            new_metric: String::new(),
+            focus_mode: false,
+            show_help: false,
+            weigh_in_due: false,
+            revealed: HashSet::new(),
+            hide_locked: false,
+            pending_private_edit: None,
+            private_edit_focus: false,
+            show_review: false,
+            toast: None,
         }
     }
     pub fn new(cc: &eframe::CreationContext<'_>) -> Self {
+        let mut app = MyApp::default();
+
         if let Some(storage) = cc.storage {
-            if let Some(mut app) = eframe::get_value::<MyApp>(storage, eframe::APP_KEY) {
-                app.curr_date = OffsetDateTime::now_local().unwrap().date();
+            // Older versions stored the app through eframe's RON helpers, read it
+            // as a plain value first so it still goes through migrate
+            let stored_app = storage.get_string(eframe::APP_KEY)
+                .and_then(|json| load_json(json.as_bytes()).ok())
+                .or_else(|| eframe::get_value::<serde_json::Value>(storage, eframe::APP_KEY)
+                    .and_then(|value| serde_json::from_value(migrate(value)).ok()))
+                .or_else(|| eframe::get_value::<MyApp>(storage, eframe::APP_KEY));
+
+            if let Some(stored_app) = stored_app {
+                app = stored_app;
+
+                // The diary itself has to be decrypted before it can be shown
+                app.locked = app.encrypted;
+
+                app.check_date();
+                app.curr_date = app.today;
                 app.mode = Mode::Main;
                 app.zoom = Zoom::Day;
-                app
-            } else {
-                MyApp::default()
+                app.sort_entries();
             }
-        } else {
-            MyApp::default()
+        }
+
+        app.apply_date_format();
+        cc.egui_ctx.set_theme(app.theme.preference());
+        egui_extras::install_image_loaders(&cc.egui_ctx);
+
+        // Ctrl+Plus/Minus drive scale_factor instead of egui's own zoom
+        cc.egui_ctx.options_mut(|options| options.zoom_with_keyboard = false);
+
+        app
+    }
+
+    pub fn apply_date_format(&mut self) {
+        // Only accept formats that can actually format a plain date, [hour] and the like can't
+        let parsed = format_description::parse_owned::<1>(&self.date_format)
+            .map_err(|error| error.to_string())
+            .and_then(|items| match self.today.format(&items) {
+                Ok(_) => Ok(items),
+                Err(error) => Err(error.to_string()),
+            });
+
+        match parsed {
+            Ok(items) => {
+                self.date_format_items = items;
+                self.axis_date_format = format_description::parse_owned::<1>(&short_date_format(&self.date_format))
+                    .unwrap_or_else(|_| default_axis_date_format());
+                self.date_format_error = None;
+            },
+            Err(error) => {
+                self.date_format_items = default_date_format_items();
+                self.axis_date_format = default_axis_date_format();
+                self.date_format_error = Some(format!("Invalid date format, using the default: {}", error));
+            },
         }
     }
 
@@ -124,514 +977,4930 @@ impl MyApp {
         }
     }
 
-    pub fn get_weights(&self) -> PlotPoints {
-        let curr_date_julian = self.curr_date.to_julian_day();
+    pub fn search_entries(&self, query: &str) -> Vec<&Entry> {
+        let query = query.to_lowercase();
 
-        let mut weight_points = vec![];
+        self.entries.iter()
+            .filter(|entry| entry.content.to_lowercase().contains(&query))
+            .collect()
+    }
 
-        match self.zoom {
-            Zoom::Day => {
-                for entry in &self.entries {
-                    if entry.weight_kg != 0.0 {
-                        let entry_date_offset = entry.date.to_julian_day() - curr_date_julian;
-                        weight_points.push([entry_date_offset as f64, entry.weight_kg as f64]);
-                    }
-                }
-            },
-            Zoom::Week => {
-                let mut curr_day;
-                let mut prev_day;
-                if self.entries.len() > 0 {
-                    curr_day = self.entries[0].date;
-                    prev_day = curr_day.prev_occurrence(curr_day.weekday());
-
-                    let mut average_weight_kg = 0.0;
-                    let mut num_weights = 0;
-
-                    for entry in &self.entries {
-                        if entry.date > prev_day && entry.date <= curr_day {
-                            // Add to the average for the week
-                            if entry.weight_kg != 0.0
-                            {
-                                average_weight_kg += entry.weight_kg;
-                                num_weights += 1;
-                            }
-                        } else {
-                            // Add point to return vector
-                            let entry_date_offset = curr_day.to_julian_day() - self.curr_date.to_julian_day();
-                            weight_points.push([entry_date_offset as f64, (average_weight_kg as f64 / num_weights as f64)]);
+    pub fn current_streak(&self) -> u32 {
+        let content_dates: HashSet<Date> = self.entries.iter()
+            .filter(|entry| entry.content.len() > 0)
+            .map(|entry| entry.date)
+            .collect();
 
-                            // Recalculate the current and previous day
-                            curr_day = prev_day;
-                            prev_day = curr_day.prev_occurrence(curr_day.weekday());
+        // Not having written yet today doesn't break the streak
+        let mut date = Some(self.curr_date);
+        if !content_dates.contains(&self.curr_date) {
+            date = self.curr_date.previous_day();
+        }
 
-                            average_weight_kg = 0.0;
-                            num_weights = 0;
-                        }
-                    }
-                }
-            },
+        let mut streak = 0;
+        while let Some(day) = date {
+            if !content_dates.contains(&day) {
+                break;
+            }
+
+            streak += 1;
+            date = day.previous_day();
         }
 
-        PlotPoints::new(weight_points)
+        streak
     }
 
-    pub fn get_waists(&self) -> PlotPoints {
-        let curr_date_julian = self.curr_date.to_julian_day();
+    pub fn days_since_last_content(&self) -> Option<i64> {
+        // Measured from the real date, browsing the past shouldn't trigger it
+        self.entries.iter()
+            .filter(|entry| entry.date <= self.today && entry.content.len() > 0)
+            .map(|entry| (self.today - entry.date).whole_days())
+            .min()
+    }
 
-        let mut waist_points = vec![];
+    pub fn total_words(&self) -> usize {
+        self.entries.iter()
+            .map(|entry| entry.content.split_whitespace().count())
+            .sum()
+    }
 
-        match self.zoom {
-            Zoom::Day => {
-                for entry in &self.entries {
-                    if entry.waist_cm != 0.0 {
-                        let entry_date_offset = entry.date.to_julian_day() - curr_date_julian;
-                        waist_points.push([entry_date_offset as f64, entry.waist_cm as f64]);
-                    }
-                }
-            },
+    pub fn next_content_entry(&self, from: Date) -> Option<Date> {
+        self.entries.iter()
+            .filter(|entry| entry.date > from && entry.content.len() > 0)
+            .map(|entry| entry.date)
+            .min()
+    }
 
-            Zoom::Week => {
-                let mut curr_day;
-                let mut prev_day;
-                if self.entries.len() > 0 {
-                    curr_day = self.entries[0].date;
-                    prev_day = curr_day.prev_occurrence(curr_day.weekday());
+    pub fn prev_content_entry(&self, from: Date) -> Option<Date> {
+        self.entries.iter()
+            .filter(|entry| entry.date < from && entry.content.len() > 0)
+            .map(|entry| entry.date)
+            .max()
+    }
 
-                    let mut average_waist_cm = 0.0;
-                    let mut num_waists = 0;
+    // Days with a weight as (julian day, kg), oldest first so rows can binary search it
+    pub fn weight_readings(&self) -> Vec<(i32, f32)> {
+        let mut readings: Vec<(i32, f32)> = self.entries.iter()
+            .filter(|entry| entry.weight_kg() != 0.0)
+            .map(|entry| (entry.date.to_julian_day(), entry.weight_kg()))
+            .collect();
+        readings.sort_by_key(|(julian, _)| *julian);
 
-                    for entry in &self.entries {
-                        if entry.date > prev_day && entry.date <= curr_day {
-                            // Add to the average for the week
-                            if entry.waist_cm != 0.0
-                            {
-                                average_waist_cm += entry.waist_cm;
-                                num_waists += 1;
-                            }
-                        } else {
-                            // Add point to return vector
-                            let entry_date_offset = curr_day.to_julian_day() - self.curr_date.to_julian_day();
-                            waist_points.push([entry_date_offset as f64, (average_waist_cm as f64 / num_waists as f64)]);
+        readings
+    }
 
-                            // Recalculate the current and previous day
-                            curr_day = prev_day;
-                            prev_day = curr_day.prev_occurrence(curr_day.weekday());
+    pub fn weight_delta(readings: &[(i32, f32)], date: Date) -> Option<f32> {
+        let date_julian = date.to_julian_day();
+        let index = readings.partition_point(|(julian, _)| *julian < date_julian);
 
-                            average_waist_cm = 0.0;
-                            num_waists = 0;
+        let &(julian, weight_kg) = readings.get(index)?;
+        if julian != date_julian {
+            return None;
+        }
+        let &(_, previous) = readings.get(index.checked_sub(1)?)?;
+
+        Some(weight_kg - previous)
+    }
+
+    pub fn count_replace_matches(&self, find: &str, ignore_case: bool) -> usize {
+        if find.is_empty() {
+            return 0;
+        }
+
+        self.entries.iter()
+            .filter(|entry| {
+                if ignore_case {
+                    replace_ignore_case(&entry.content, find, "") != entry.content
+                } else {
+                    entry.content.contains(find)
+                }
+            })
+            .count()
+    }
+
+    pub fn replace_in_entries(&mut self, find: &str, replace: &str, ignore_case: bool) -> usize {
+        let num_changed = self.count_replace_matches(find, ignore_case);
+        if num_changed == 0 {
+            return 0;
+        }
+
+        self.push_snapshot();
+
+        for entry in &mut self.entries {
+            if ignore_case {
+                entry.content = replace_ignore_case(&entry.content, find, replace);
+            } else {
+                entry.content = entry.content.replace(find, replace);
+            }
+        }
+
+        num_changed
+    }
+
+    pub fn on_this_day(&self, today: Date) -> Vec<&Entry> {
+        self.entries.iter()
+            .filter(|entry| entry.date.year() < today.year())
+            .filter(|entry| entry.date.month() == today.month() && entry.date.day() == today.day())
+            .filter(|entry| entry.content.len() > 0)
+            .collect()
+    }
+
+    pub fn pinned_entries(&self) -> Vec<&Entry> {
+        self.entries.iter()
+            .filter(|entry| entry.pinned)
+            .collect()
+    }
+
+    pub fn all_tags(&self) -> Vec<String> {
+        let mut tags: Vec<String> = self.entries.iter()
+            .flat_map(|entry| entry.tags.iter().cloned())
+            .collect();
+        tags.sort();
+        tags.dedup();
+
+        tags
+    }
+
+    pub fn entries_with_tag(&self, tag: &str) -> Vec<&Entry> {
+        self.entries.iter()
+            .filter(|entry| entry.tags.iter().any(|entry_tag| entry_tag == tag))
+            .collect()
+    }
+
+    fn get_measurement_points(&self, measurement: impl Fn(&Entry) -> f32) -> PlotPoints {
+        // Week zoom shares its buckets with the weekly average toggle
+        if matches!(self.zoom, Zoom::Week) {
+            return self.get_weekly_points(measurement);
+        }
+
+        let curr_date_julian = self.curr_date.to_julian_day();
+
+        let points: Vec<[f64; 2]> = self.entries.iter()
+            .filter(|entry| measurement(entry) != 0.0)
+            .map(|entry| {
+                let entry_date_offset = entry.date.to_julian_day() - curr_date_julian;
+                [entry_date_offset as f64, measurement(entry) as f64]
+            })
+            .collect();
+
+        PlotPoints::new(points)
+    }
+
+    // Weights within span days either side of date, x is the offset from it
+    pub fn weights_around(readings: &[(i32, f32)], date: Date, span: i64) -> PlotPoints {
+        let date_julian = date.to_julian_day() as i64;
+        let start = readings.partition_point(|(julian, _)| (*julian as i64) < date_julian - span);
+        let end = readings.partition_point(|(julian, _)| (*julian as i64) <= date_julian + span);
+
+        let points: Vec<[f64; 2]> = readings[start..end].iter()
+            .map(|&(julian, weight_kg)| [(julian as i64 - date_julian) as f64, weight_kg as f64])
+            .collect();
+
+        PlotPoints::new(points)
+    }
+
+    pub fn weight_unit_label(&self) -> &str {
+        self.units.weight_suffix().trim()
+    }
+
+    pub fn length_unit_label(&self) -> &str {
+        self.units.length_suffix().trim()
+    }
+
+    pub fn get_weights(&self) -> PlotPoints {
+        self.get_measurement_points(|entry| entry.weight_kg())
+    }
+
+    pub fn get_waists(&self) -> PlotPoints {
+        self.get_measurement_points(|entry| entry.waist_cm())
+    }
+
+    pub fn get_sleep_points(&self) -> PlotPoints {
+        self.get_measurement_points(|entry| entry.sleep_hours())
+    }
+
+    fn get_weekly_points(&self, measurement: impl Fn(&Entry) -> f32) -> PlotPoints {
+        let curr_date_julian = self.curr_date.to_julian_day();
+
+        // Sum and count per week, weeks without measurements never get a slot
+        let mut weeks: BTreeMap<Date, (f32, u32)> = BTreeMap::new();
+        for entry in &self.entries {
+            if measurement(entry) != 0.0 {
+                let sum = weeks.entry(self.week_start_of(entry.date)).or_insert((0.0, 0));
+                sum.0 += measurement(entry);
+                sum.1 += 1;
+            }
+        }
+
+        let points: Vec<[f64; 2]> = weeks.iter()
+            .map(|(&week_start, &(sum, count))| {
+                let midpoint = (week_start.to_julian_day() - curr_date_julian) as f64 + 3.0;
+                [midpoint, (sum / count as f32) as f64]
+            })
+            .collect();
+
+        PlotPoints::new(points)
+    }
+
+    pub fn get_weights_weekly(&self) -> PlotPoints {
+        self.get_weekly_points(|entry| entry.weight_kg())
+    }
+
+    pub fn get_waists_weekly(&self) -> PlotPoints {
+        self.get_weekly_points(|entry| entry.waist_cm())
+    }
+
+    fn get_optional_points(&self, measurement: impl Fn(&Entry) -> Option<f32>) -> PlotPoints {
+        if matches!(self.zoom, Zoom::Week) {
+            return self.get_measurement_points(|entry| measurement(entry).unwrap_or(0.0));
+        }
+
+        let curr_date_julian = self.curr_date.to_julian_day();
+
+        // Entries without a value break the line instead of dropping it to zero
+        let points: Vec<[f64; 2]> = self.entries.iter()
+            .map(|entry| {
+                let entry_date_offset = entry.date.to_julian_day() - curr_date_julian;
+                [entry_date_offset as f64, measurement(entry).map_or(f64::NAN, |value| value as f64)]
+            })
+            .collect();
+
+        PlotPoints::new(points)
+    }
+
+    pub fn get_hr_points(&self) -> PlotPoints {
+        self.get_optional_points(|entry| entry.resting_hr().map(|hr| hr as f32))
+    }
+
+    pub fn get_calorie_points(&self) -> PlotPoints {
+        self.get_optional_points(|entry| entry.calories().map(|calories| calories as f32))
+    }
+
+    pub fn get_metric_points(&self, name: &str) -> PlotPoints {
+        self.get_optional_points(|entry| entry.values.get(name).copied())
+    }
+
+    pub fn get_bodyfat_points(&self) -> PlotPoints {
+        self.get_optional_points(|entry| entry.body_fat_pct())
+    }
+
+    pub fn x_offset_range(&self) -> (f64, f64) {
+        let curr_date_julian = self.curr_date.to_julian_day();
+
+        // Always covers the selected day, and stretches for entries on either side of it, future ones too
+        let offsets = self.entries.iter().map(|entry| (entry.date.to_julian_day() - curr_date_julian) as f64);
+        let min_x = offsets.clone().fold(0.0, f64::min);
+        let max_x = offsets.fold(0.0, f64::max);
+
+        (min_x, max_x)
+    }
+
+    // Days logged in each of the last 12 months, oldest first
+    pub fn get_entries_per_month(&self) -> Vec<(String, u64)> {
+        (-11..=0)
+            .map(|offset| {
+                let (year, month) = shift_month(self.today.year(), self.today.month(), offset);
+                // Duplicate entries on one day still count as a single day
+                let count = self.entries.iter()
+                    .filter(|entry| entry.date.year() == year && entry.date.month() == month && entry.has_data())
+                    .map(|entry| entry.date)
+                    .collect::<HashSet<Date>>()
+                    .len();
+
+                let name: String = month.to_string().chars().take(3).collect();
+                (format!("{} {:02}", name, year.rem_euclid(100)), count as u64)
+            })
+            .collect()
+    }
+
+    fn entries_per_month_ui(&self, ui: &mut egui::Ui) {
+        let months = self.get_entries_per_month();
+        let bars: Vec<Bar> = months.iter().enumerate()
+            .map(|(idx, (name, count))| Bar::new(idx as f64, *count as f64).name(name).width(0.7))
+            .collect();
+        let names: Vec<String> = months.into_iter().map(|(name, _)| name).collect();
+
+        Plot::new("entries_per_month").view_aspect(3.0)
+            .allow_boxed_zoom(false)
+            .allow_double_click_reset(false)
+            .allow_drag(false)
+            .allow_scroll(false)
+            .allow_zoom(false)
+            .show_x(false)
+            .include_y(0.0)
+            .show_background(false)
+            .x_axis_formatter(move |grid_mark, _| {
+                // Only whole months get a label
+                if grid_mark.value >= 0.0 && grid_mark.value.fract() == 0.0 {
+                    names.get(grid_mark.value as usize).cloned().unwrap_or_default()
+                } else {
+                    String::new()
+                }
+            })
+            .y_axis_label("Days logged")
+            .show(ui, |plot_ui| plot_ui.bar_chart(BarChart::new("Days logged", bars).color(Color32::LIGHT_BLUE)));
+    }
+
+    pub fn get_whr_points(&self) -> PlotPoints {
+        let curr_date_julian = self.curr_date.to_julian_day();
+
+        let mut whr_points = vec![];
+
+        if self.height_cm > 0.0 {
+            for entry in &self.entries {
+                if entry.waist_cm() != 0.0 {
+                    let entry_date_offset = entry.date.to_julian_day() - curr_date_julian;
+                    whr_points.push([entry_date_offset as f64, (entry.waist_cm() / self.height_cm) as f64]);
+                }
+            }
+        }
+
+        PlotPoints::new(whr_points)
+    }
+
+    pub fn latest_whr(&self) -> Option<f32> {
+        if self.height_cm == 0.0 {
+            return None;
+        }
+
+        self.entries.iter()
+            .filter(|entry| entry.waist_cm() != 0.0)
+            .max_by_key(|entry| entry.date)
+            .map(|entry| entry.waist_cm() / self.height_cm)
+    }
+
+    pub fn get_bmi_points(&self) -> PlotPoints {
+        let curr_date_julian = self.curr_date.to_julian_day();
+        let height_m = self.height_cm / 100.0;
+
+        let mut bmi_points = vec![];
+
+        if height_m > 0.0 {
+            for entry in &self.entries {
+                if entry.weight_kg() != 0.0 {
+                    let entry_date_offset = entry.date.to_julian_day() - curr_date_julian;
+                    bmi_points.push([entry_date_offset as f64, (entry.weight_kg() / (height_m * height_m)) as f64]);
+                }
+            }
+        }
+
+        PlotPoints::new(bmi_points)
+    }
+
+    pub fn get_weight_moving_average(&self, window: usize) -> PlotPoints {
+        let curr_date_julian = self.curr_date.to_julian_day();
+        let window = window.max(1) as i32;
+
+        let mut weights: Vec<(i32, f32)> = self.entries.iter()
+            .filter(|entry| entry.weight_kg() != 0.0)
+            .map(|entry| (entry.date.to_julian_day(), entry.weight_kg()))
+            .collect();
+        weights.sort_by_key(|(day, _)| *day);
+
+        let mut average_points = vec![];
+
+        for (day, _) in &weights {
+            // Average every weight in the trailing window ending on this day
+            let window_weights: Vec<f32> = weights.iter()
+                .filter(|(other_day, _)| *other_day <= *day && *other_day > *day - window)
+                .map(|(_, weight_kg)| *weight_kg)
+                .collect();
+
+            let average_weight_kg = window_weights.iter().sum::<f32>() / window_weights.len() as f32;
+            average_points.push([(day - curr_date_julian) as f64, average_weight_kg as f64]);
+        }
+
+        PlotPoints::new(average_points)
+    }
+
+    pub fn first_weight(&self) -> Option<f32> {
+        self.entries.iter()
+            .filter(|entry| entry.weight_kg() != 0.0)
+            .min_by_key(|entry| entry.date)
+            .map(|entry| entry.weight_kg())
+    }
+
+    pub fn latest_weight(&self) -> Option<f32> {
+        self.entries.iter()
+            .filter(|entry| entry.weight_kg() != 0.0)
+            .max_by_key(|entry| entry.date)
+            .map(|entry| entry.weight_kg())
+    }
+
+    // Latest minus first weight, needs at least two readings
+    pub fn total_change(&self) -> Option<f32> {
+        let num_readings = self.entries.iter().filter(|entry| entry.weight_kg() != 0.0).count();
+        if num_readings < 2 {
+            return None;
+        }
+
+        Some(self.latest_weight()? - self.first_weight()?)
+    }
+
+    pub fn goal_reached(&self) -> bool {
+        if let (Some(goal), Some(first), Some(latest)) = (self.goal_weight_kg, self.first_weight(), self.latest_weight()) {
+            // The goal can be either below (losing) or above (gaining) the starting weight
+            if first >= goal {
+                latest <= goal
+            } else {
+                latest >= goal
+            }
+        } else {
+            false
+        }
+    }
+
+    pub fn rolling_average_weight(&self, days: i64) -> Option<f32> {
+        let start = self.curr_date - time::Duration::days(days - 1);
+
+        let weights: Vec<f32> = self.entries.iter()
+            .filter(|entry| entry.date >= start && entry.date <= self.curr_date && entry.weight_kg() != 0.0)
+            .map(|entry| entry.weight_kg())
+            .collect();
+
+        if weights.len() == 0 {
+            return None;
+        }
+
+        Some(weights.iter().sum::<f32>() / weights.len() as f32)
+    }
+
+    pub fn goal_progress(&self) -> Option<f32> {
+        let goal = self.goal_weight_kg?;
+        let first = self.first_weight()?;
+        let latest = self.latest_weight().unwrap_or(first);
+
+        if first == goal {
+            return Some(1.0);
+        }
+
+        // Works the same whether losing or gaining towards the goal
+        Some(((first - latest) / (first - goal)).clamp(0.0, 1.0))
+    }
+
+    pub fn project_goal_date(&self) -> Option<Date> {
+        let goal_weight_kg = self.goal_weight_kg? as f64;
+        let curr_date_julian = self.curr_date.to_julian_day();
+
+        // Only fit the last 60 days so old trends don't skew the projection
+        let points: Vec<(f64, f64)> = self.entries.iter()
+            .filter(|entry| entry.weight_kg() != 0.0)
+            .filter(|entry| entry.date.to_julian_day() > curr_date_julian - 60 && entry.date.to_julian_day() <= curr_date_julian)
+            .map(|entry| (entry.date.to_julian_day() as f64, entry.weight_kg() as f64))
+            .collect();
+
+        if points.len() < 3 {
+            return None;
+        }
+
+        // Least-squares fit of weight against julian day
+        let num_points = points.len() as f64;
+        let mean_day = points.iter().map(|(day, _)| day).sum::<f64>() / num_points;
+        let mean_weight = points.iter().map(|(_, weight)| weight).sum::<f64>() / num_points;
+
+        let mut sum_xx = 0.0;
+        let mut sum_xy = 0.0;
+        for (day, weight) in &points {
+            sum_xx += (day - mean_day) * (day - mean_day);
+            sum_xy += (day - mean_day) * (weight - mean_weight);
+        }
+
+        if sum_xx == 0.0 {
+            return None;
+        }
+
+        let slope = sum_xy / sum_xx;
+        let intercept = mean_weight - slope * mean_day;
+
+        // The trend has to be heading towards the goal
+        let curr_weight = intercept + slope * curr_date_julian as f64;
+        if (goal_weight_kg - curr_weight) * slope <= 0.0 {
+            return None;
+        }
+
+        let goal_day = (goal_weight_kg - intercept) / slope;
+        if !goal_day.is_finite() || goal_day > i32::MAX as f64 {
+            return None;
+        }
+
+        Date::from_julian_day(goal_day.round() as i32).ok()
+    }
+
+    pub fn stats_for_range(&self, start: Date, end: Date) -> EntryStats {
+        let entries: Vec<&Entry> = self.entries.iter()
+            .filter(|entry| entry.date >= start && entry.date <= end)
+            .collect();
+
+        // Zero means not measured, so those are left out of the averages
+        let weights: Vec<f32> = entries.iter().map(|entry| entry.weight_kg()).filter(|weight_kg| *weight_kg != 0.0).collect();
+        let waists: Vec<f32> = entries.iter().map(|entry| entry.waist_cm()).filter(|waist_cm| *waist_cm != 0.0).collect();
+
+        let days_journaled = entries.iter()
+            .filter(|entry| !entry.content.trim().is_empty())
+            .count();
+
+        EntryStats {
+            weight_kg: MeasurementStats::from_values(&weights),
+            waist_cm: MeasurementStats::from_values(&waists),
+            days_journaled,
+        }
+    }
+
+    pub fn get_max_weight(&self) -> f32 {
+        let mut max_weight = 0.0;
+
+        for entry in &self.entries {
+            if entry.weight_kg() > max_weight {
+                max_weight = entry.weight_kg();
+            }
+        }
+
+        max_weight
+    }
+
+    pub fn get_max_waist(&self) -> f32 {
+        let mut max_waist = 0.0;
+
+        for entry in &self.entries {
+            if entry.waist_cm() > max_waist {
+                max_waist = entry.waist_cm();
+            }
+        }
+
+        max_waist
+    }
+
+    pub fn import_csv(&mut self, path: &str) -> Result<usize, Box<dyn Error>> {
+        let mut reader = csv::ReaderBuilder::new()
+            .has_headers(false)
+            .flexible(true)
+            .from_path(path)?;
+        let format = format_description::parse("[year]-[month]-[day]")?;
+
+        // The whole import is a single undo step
+        self.push_snapshot();
+
+        let mut num_imported = 0;
+        self.import_errors.clear();
+
+        for record in reader.records() {
+            let record = record?;
+            let line = record.position().map_or(0, |position| position.line());
+            let date_field = record.get(0).unwrap_or("").trim();
+
+            // Skip the header row
+            if date_field == "date" {
+                continue;
+            }
+
+            // Bad rows are reported but don't stop the rest of the import
+            let date = match Date::parse(date_field, &format) {
+                Ok(date) => date,
+                Err(_) => {
+                    self.import_errors.push(format!("Line {}: invalid date \"{}\"", line, date_field));
+                    continue;
+                }
+            };
+
+            let (weight_kg, waist_cm) = match (parse_measurement(record.get(1)), parse_measurement(record.get(2))) {
+                (Ok(weight_kg), Ok(waist_cm)) if weight_kg.is_finite() && waist_cm.is_finite() => {
+                    (weight_kg.clamp(0.0, MAX_WEIGHT_KG), waist_cm.clamp(0.0, MAX_WAIST_CM))
+                },
+                _ => {
+                    self.import_errors.push(format!("Line {}: invalid measurement", line));
+                    continue;
+                }
+            };
+
+            let content = record.get(3).unwrap_or("").to_string();
+
+            self.update_entry(date, |entry| {
+                entry.set_value(WEIGHT, weight_kg);
+                entry.set_value(WAIST, waist_cm);
+                entry.content = content;
+            });
+
+            num_imported += 1;
+        }
+
+        self.sort_entries();
+
+        Ok(num_imported)
+    }
+
+    // Health app exports have a Date,Weight header and can log several readings a day
+    pub fn import_health_csv(&mut self, path: &str) -> Result<(usize, Option<(Date, Date)>), Box<dyn Error>> {
+        let mut reader = csv::ReaderBuilder::new()
+            .flexible(true)
+            .from_path(path)?;
+        let format = format_description::parse("[year]-[month]-[day]")?;
+
+        let headers: Vec<String> = reader.headers()?.iter().map(|header| header.trim().to_lowercase()).collect();
+        let date_column = headers.iter().position(|header| header.starts_with("date")).unwrap_or(0);
+        let weight_column = headers.iter().position(|header| header.contains("weight")).unwrap_or(1);
+
+        // Later readings of the same day replace earlier ones
+        let mut readings = BTreeMap::new();
+        self.import_errors.clear();
+
+        for record in reader.records() {
+            let record = record?;
+            let line = record.position().map_or(0, |position| position.line());
+            let timestamp = record.get(date_column).unwrap_or("").trim();
+
+            // Only the calendar date of the ISO-8601 timestamp matters
+            let date = match timestamp.get(..10).map(|date_field| Date::parse(date_field, &format)) {
+                Some(Ok(date)) => date,
+                _ => {
+                    self.import_errors.push(format!("Line {}: invalid date \"{}\"", line, timestamp));
+                    continue;
+                }
+            };
+
+            match parse_measurement(record.get(weight_column)) {
+                Ok(weight_kg) if weight_kg.is_finite() && weight_kg > 0.0 => {
+                    readings.insert(date, weight_kg.clamp(0.0, MAX_WEIGHT_KG));
+                },
+                _ => self.import_errors.push(format!("Line {}: invalid weight", line)),
+            }
+        }
+
+        let range = match (readings.keys().next(), readings.keys().next_back()) {
+            (Some(&first), Some(&last)) => Some((first, last)),
+            _ => None,
+        };
+
+        // Same as the diary import, one undo step for the lot
+        self.push_snapshot();
+        for (&date, &weight_kg) in &readings {
+            self.update_entry(date, |entry| entry.set_value(WEIGHT, weight_kg));
+        }
+        self.sort_entries();
+
+        Ok((readings.len(), range))
+    }
+
+    // Imported rows land on the existing entry for their day, or a new one
+    fn update_entry(&mut self, date: Date, update: impl FnOnce(&mut Entry)) {
+        if let Some(entry) = self.entries.iter_mut().find(|entry| entry.date == date) {
+            update(entry);
+        } else {
+            let mut entry = Entry::new(date);
+            update(&mut entry);
+
+            self.insert_entry(entry);
+        }
+    }
+
+    pub fn save_to_file(&mut self) -> io::Result<()> {
+        let path = Path::new(&self.path_to_file);
+
+        if let Some(parent) = path.parent() {
+            if !parent.as_os_str().is_empty() && !parent.is_dir() {
+                return Err(io::Error::new(io::ErrorKind::NotFound, format!("Folder {} does not exist", parent.display())));
+            }
+        }
+
+        let json = serde_json::to_vec_pretty(self).map_err(io::Error::other)?;
+
+        if self.encrypted {
+            if self.password.is_empty() {
+                return Err(io::Error::other("Set a password to save an encrypted diary"));
+            }
+
+            // The key only gets derived again when the password changes
+            if self.derived_key.as_ref().is_none_or(|(password, _)| *password != self.password) {
+                self.derived_key = Some((self.password.clone(), crypto::DerivedKey::new(&self.password)?));
+            }
+            let (_, key) = self.derived_key.as_ref().unwrap();
+
+            fs::write(path, crypto::encrypt(&json, key)?)
+        } else {
+            fs::write(path, json)
+        }
+    }
+
+    pub fn unlock(&mut self) -> io::Result<()> {
+        let data = fs::read(&self.path_to_file)?;
+        let json = crypto::decrypt(&data, &self.password)?;
+        let app = load_json(&json).map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))?;
+
+        self.entries = app.entries;
+        self.sort_entries();
+        self.sections = app.sections;
+        self.archived_tasks = app.archived_tasks;
+
+        self.locked = false;
+        self.unlock_error = None;
+
+        // Recurring tasks could only be reset now that the real sections are here
+        self.check_date();
+        self.curr_date = self.today;
+
+        Ok(())
+    }
+
+    pub fn switch_profile(&mut self, profile: &str) -> io::Result<()> {
+        if profile == self.active_profile {
+            return Ok(());
+        }
+
+        let path = self.profile_paths.get(profile).cloned().unwrap_or_else(|| profile_path(profile));
+
+        // Read the other diary first, so a failure leaves this one untouched
+        let data = if Path::new(&path).exists() { Some(fs::read(&path)?) } else { None };
+        let stored_app = match &data {
+            Some(data) if !crypto::is_encrypted(data) => {
+                Some(load_json(data).map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))?)
+            },
+            _ => None,
+        };
+
+        // Save what we have before the diary gets swapped out
+        if !self.locked {
+            self.rotate_backups().and_then(|_| self.save_to_file())?;
+        }
+
+        self.profile_paths.insert(self.active_profile.clone(), self.path_to_file.clone());
+        self.profile_paths.remove(profile);
+        self.active_profile = profile.to_string();
+        self.path_to_file = path;
+
+        self.password.clear();
+        self.unlock_error = None;
+        self.save_error = None;
+        self.undo_stack.clear();
+        self.pending_delete = None;
+        self.scroll_to_date = None;
+        self.tag_filter = None;
+        self.search_query.clear();
+        self.mode = Mode::Main;
+        self.curr_date = self.today;
+
+        match (data, stored_app) {
+            (None, _) => {
+                let fresh = MyApp::default();
+                self.entries = fresh.entries;
+                self.sections = fresh.sections;
+                self.archived_tasks = fresh.archived_tasks;
+                self.encrypted = false;
+                self.locked = false;
+            },
+            (Some(_), Some(app)) => {
+                self.entries = app.entries;
+                self.sort_entries();
+                self.sections = app.sections;
+                self.archived_tasks = app.archived_tasks;
+                self.encrypted = false;
+                self.locked = false;
+            },
+            // The password dialog takes over from here
+            (Some(_), None) => {
+                self.entries.clear();
+                self.sections.clear();
+                self.archived_tasks.clear();
+                self.encrypted = true;
+                self.locked = true;
+            },
+        }
+
+        Ok(())
+    }
+
+    pub fn add_profile(&mut self) -> io::Result<()> {
+        let profile = self.new_profile.trim().to_lowercase();
+
+        if profile.is_empty() || !profile.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-') {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "Profile names can only use letters, digits, - and _"));
+        }
+        if self.profiles.contains(&profile) {
+            return Err(io::Error::new(io::ErrorKind::AlreadyExists, format!("Profile {} already exists", profile)));
+        }
+
+        self.profiles.push(profile.clone());
+        self.new_profile.clear();
+
+        self.switch_profile(&profile)
+    }
+
+    pub fn rotate_backups(&self) -> io::Result<()> {
+        if self.backup_count == 0 || !Path::new(&self.path_to_file).exists() {
+            return Ok(());
+        }
+
+        // Shift the older backups up one slot, the oldest one gets overwritten
+        for index in (1..self.backup_count).rev() {
+            let from = format!("{}.{}", self.path_to_file, index);
+            if Path::new(&from).exists() {
+                fs::rename(&from, format!("{}.{}", self.path_to_file, index + 1))?;
+            }
+        }
+
+        fs::copy(&self.path_to_file, format!("{}.1", self.path_to_file))?;
+
+        Ok(())
+    }
+
+    // The daily trend would only clutter one point per week
+    fn weekly_charts(&self) -> bool {
+        self.weekly_average || matches!(self.zoom, Zoom::Week)
+    }
+
+    // The weight chart, or weight and waist overlaid, on its own so "Save chart" can draw it again offscreen
+    fn weight_chart_ui(&self, ui: &mut egui::Ui, width: f32) -> egui::InnerResponse<Option<PlotPoint>> {
+        let curr_date = self.curr_date;
+        let (min_x, max_x) = self.x_offset_range();
+        let axis_format = &self.axis_date_format;
+        let label_format = &self.date_format_items;
+        let weight_factor = self.units.weight_factor() as f64;
+        let length_factor = self.units.length_factor() as f64;
+        let weight_suffix = self.units.weight_suffix();
+        let length_suffix = self.units.length_suffix();
+        let weight_axis_label = format!("Weight [{}]", self.weight_unit_label());
+        let waist_axis_label = format!("Waist [{}]", self.length_unit_label());
+
+        let weekly = self.weekly_charts();
+        let (weight_points, waist_points) = if weekly {
+            (self.get_weights_weekly(), self.get_waists_weekly())
+        } else {
+            (self.get_weights(), self.get_waists())
+        };
+
+        let (mut min_weight_bound, mut max_weight_bound) = measurement_bounds(&weight_points, self.get_max_weight());
+
+        // Make sure the goal line is always in view
+        if let Some(goal_weight_kg) = self.goal_weight_kg {
+            min_weight_bound = min_weight_bound.min(goal_weight_kg as f64 - 1.0);
+            max_weight_bound = max_weight_bound.max(goal_weight_kg as f64 + 1.0);
+        }
+        let (min_waist_bound, max_waist_bound) = measurement_bounds(&waist_points, self.get_max_waist());
+
+        let weight_line = Line::new("Weight", weight_points)
+            .width(1.5)
+            .color(color_from(self.weight_color));
+        let average_line = (!weekly).then(|| {
+            Line::new("Trend", self.get_weight_moving_average(self.trend_window))
+                .width(3.0)
+                .color(Color32::ORANGE)
+        });
+
+        // Kept in a scope so the response covers the axes too
+        ui.scope(|ui| {
+            if self.overlay_charts && !self.show_bmi {
+                // For the overlay the waist is rescaled onto the weight axis
+                let waist_scale = (max_weight_bound - min_weight_bound) / (max_waist_bound - min_waist_bound);
+                let scaled_waist_points: PlotPoints = waist_points.points().iter()
+                    .map(|point| [point.x, min_weight_bound + (point.y - min_waist_bound) * waist_scale])
+                    .collect();
+                let scaled_waist_line = Line::new("Waist", scaled_waist_points)
+                    .width(1.5)
+                    .color(color_from(self.waist_color));
+
+                let waist_axis = AxisHints::new_y()
+                    .label(waist_axis_label.as_str())
+                    .placement(HPlacement::Right)
+                    .formatter(move |grid_mark, _| format!("{:.0}", (min_waist_bound + (grid_mark.value - min_weight_bound) / waist_scale) * length_factor));
+
+                Plot::new("overlay").view_aspect(3.2)
+                    .width(width)
+                    .allow_boxed_zoom(false)
+                    .allow_double_click_reset(false)
+                    .allow_drag(false)
+                    .allow_scroll(false)
+                    .allow_zoom(false)
+                    .show_x(false)
+                    .include_x(min_x)
+                    .include_x(max_x)
+                    .default_y_bounds(min_weight_bound, max_weight_bound)
+                    .show_background(false)
+                    .legend(Legend::default())
+                    .x_axis_formatter(move |grid_mark, range| x_axis_dates(curr_date, axis_format, grid_mark, range))
+                    .label_formatter(move |name, value| {
+                        if name == "Waist" {
+                            let waist = PlotPoint::new(value.x, (min_waist_bound + (value.y - min_weight_bound) / waist_scale) * length_factor);
+                            point_label(curr_date, label_format, name, &waist, length_suffix)
+                        } else {
+                            let weight = PlotPoint::new(value.x, value.y * weight_factor);
+                            point_label(curr_date, label_format, name, &weight, weight_suffix)
+                        }
+                    })
+                    .custom_y_axes(vec![
+                        AxisHints::new_y()
+                            .label(weight_axis_label.as_str())
+                            .formatter(move |grid_mark, _| y_axis_scaled(weight_factor, grid_mark)),
+                        waist_axis,
+                    ])
+                    .show(ui, |plot_ui| {
+                        if let Some(average_line) = average_line {
+                            plot_ui.line(average_line);
+                        }
+                        plot_ui.line(weight_line);
+                        plot_ui.line(scaled_waist_line);
+
+                        if let Some(goal_weight_kg) = self.goal_weight_kg {
+                            plot_ui.hline(HLine::new("Goal", goal_weight_kg as f64)
+                                .color(Color32::GREEN)
+                                .style(LineStyle::dashed_loose()));
+                        }
+
+                        clicked_point(plot_ui)
+                    })
+                    .inner
+            } else {
+                Plot::new("weight").view_aspect(1.6)
+                    .width(width)
+                    .allow_boxed_zoom(false)
+                    .allow_double_click_reset(false)
+                    .allow_drag(false)
+                    .allow_scroll(false)
+                    .allow_zoom(false)
+                    .show_x(false)
+                    .include_x(min_x)
+                    .include_x(max_x)
+                    .default_y_bounds(min_weight_bound, max_weight_bound)
+                    .show_background(false)
+                    .legend(Legend::default())
+                    .x_axis_formatter(move |grid_mark, range| x_axis_dates(curr_date, axis_format, grid_mark, range))
+                    .label_formatter(move |name, value| point_label(curr_date, label_format, name, &PlotPoint::new(value.x, value.y * weight_factor), weight_suffix))
+                    .y_axis_formatter(move |grid_mark, _| y_axis_scaled(weight_factor, grid_mark))
+                    .y_axis_label(weight_axis_label.as_str())
+                    .show(ui, |plot_ui| {
+                        // Trend goes first so it is drawn behind the raw weights
+                        if let Some(average_line) = average_line {
+                            plot_ui.line(average_line);
+                        }
+                        plot_ui.line(weight_line);
+
+                        if let Some(goal_weight_kg) = self.goal_weight_kg {
+                            plot_ui.hline(HLine::new("Goal", goal_weight_kg as f64)
+                                .color(Color32::GREEN)
+                                .style(LineStyle::dashed_loose()));
+                        }
+
+                        clicked_point(plot_ui)
+                    })
+                    .inner
+            }
+        })
+    }
+
+    // Draws the weight chart again in a context of its own, sized so it comes out chart_export_width pixels wide
+    pub fn save_chart_png(&self, ctx: &egui::Context, path: &str) -> Result<(), Box<dyn Error>> {
+        let size = self.weight_chart_rect.ok_or("The weight chart isn't visible")?.size();
+        let pixels_per_point = self.chart_export_width.max(1) as f32 / size.x;
+
+        let offscreen = egui::Context::default();
+        offscreen.set_theme(ctx.theme());
+        offscreen.set_style(ctx.style());
+
+        let mut raw_input = egui::RawInput {
+            screen_rect: Some(egui::Rect::from_min_size(egui::Pos2::ZERO, size)),
+            max_texture_side: Some(8192),
+            ..Default::default()
+        };
+        raw_input.viewports.entry(egui::ViewportId::ROOT).or_default().native_pixels_per_point = Some(pixels_per_point);
+
+        // The second pass has the plot and legend laid out with the first one's measurements
+        let mut textures = HashMap::new();
+        let mut output = None;
+        for _ in 0..2 {
+            let full_output = offscreen.run(raw_input.clone(), |ctx| {
+                egui::CentralPanel::default()
+                    .frame(egui::Frame::NONE.fill(ctx.style().visuals.panel_fill))
+                    .show(ctx, |ui| self.weight_chart_ui(ui, size.x));
+            });
+            raster::update_textures(&mut textures, &full_output.textures_delta);
+            output = Some(full_output);
+        }
+        let output = output.unwrap();
+
+        let primitives = offscreen.tessellate(output.shapes, output.pixels_per_point);
+        let image_size = [(size.x * pixels_per_point).round() as u32, (size.y * pixels_per_point).round() as u32];
+        raster::rasterize(&primitives, &textures, image_size, output.pixels_per_point).save(path)?;
+
+        Ok(())
+    }
+
+    // A plain JSON copy of everything, encrypted or not, to move the diary between machines
+    pub fn export_all(&self, path: &str) -> io::Result<()> {
+        let json = serde_json::to_vec_pretty(self).map_err(io::Error::other)?;
+
+        fs::write(path, json)
+    }
+
+    fn export_backup(&mut self) {
+        self.backup_message = match self.export_all(&self.backup_path) {
+            Ok(()) => format!("Exported to {}", self.backup_path),
+            Err(error) => format!("Export failed: {}", error),
+        };
+    }
+
+    pub fn import_all(&mut self, path: &str, merge: bool) -> io::Result<usize> {
+        let data = fs::read(path)?;
+        if crypto::is_encrypted(&data) {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "Encrypted diary files can't be imported, use Export all instead"));
+        }
+        let mut app = load_json(&data).map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))?;
+
+        self.push_snapshot();
+
+        if merge {
+            // Entries and sections already here win over the imported ones
+            let mut num_added = 0;
+            for entry in app.entries {
+                if !self.entries.iter().any(|other| other.date == entry.date) {
+                    self.insert_entry(entry);
+                    num_added += 1;
+                }
+            }
+            for section in app.sections {
+                if !self.sections.iter().any(|other| other.title == section.title) {
+                    self.sections.push(section);
+                }
+            }
+            for archived_task in app.archived_tasks {
+                if !self.archived_tasks.contains(&archived_task) {
+                    self.archived_tasks.push(archived_task);
+                }
+            }
+
+            return Ok(num_added);
+        }
+
+        // The diary file, profiles, window and password stay those of this machine
+        app.path_to_file = std::mem::take(&mut self.path_to_file);
+        app.profiles = std::mem::take(&mut self.profiles);
+        app.active_profile = std::mem::take(&mut self.active_profile);
+        app.profile_paths = std::mem::take(&mut self.profile_paths);
+        app.password = std::mem::take(&mut self.password);
+        app.undo_stack = std::mem::take(&mut self.undo_stack);
+        app.encrypted = self.encrypted;
+        app.window_size = self.window_size;
+        app.window_pos = self.window_pos;
+        app.window_restored = true;
+        app.scale_factor = self.scale_factor;
+        app.today = self.today;
+        app.curr_date = self.today;
+        app.mode = Mode::Main;
+        app.show_settings = true;
+
+        let num_entries = app.entries.len();
+        *self = app;
+        self.sort_entries();
+        self.apply_date_format();
+
+        Ok(num_entries)
+    }
+
+    pub fn export_markdown(&self, path: &str) -> io::Result<()> {
+        let format = self.date_format_items.clone();
+
+        let mut entries: Vec<&Entry> = self.entries.iter()
+            .filter(|entry| entry.content.len() > 0 || entry.weight_kg() != 0.0 || entry.waist_cm() != 0.0
+                || self.metrics.iter().any(|metric| entry.values.contains_key(&metric.name)))
+            .collect();
+        entries.sort_by(|a, b| b.date.cmp(&a.date));
+
+        let mut markdown = String::new();
+
+        for entry in entries {
+            markdown.push_str(&format!("## {}\n\n", entry.date.format(&format).unwrap()));
+
+            let mut stats = vec![];
+            if entry.weight_kg() != 0.0 {
+                stats.push(format!("{:.1} kg", entry.weight_kg()));
+            }
+            if entry.waist_cm() != 0.0 {
+                stats.push(format!("{:.1} cm", entry.waist_cm()));
+            }
+            for metric in &self.metrics {
+                if let Some(value) = entry.value(&metric.name) {
+                    stats.push(format!("{} {:.1} {}", metric.name, value, metric.unit));
+                }
+            }
+            if stats.len() > 0 {
+                markdown.push_str(&format!("*{}*\n\n", stats.join(" · ")));
+            }
+
+            if entry.is_hidden(&self.revealed) {
+                markdown.push_str("[locked]\n\n");
+            } else if entry.content.len() > 0 {
+                markdown.push_str(entry.content.trim_end());
+                markdown.push_str("\n\n");
+            }
+        }
+
+        fs::write(path, markdown)
+    }
+
+    fn search_results_ui(&self, ui: &mut egui::Ui) {
+        let format = self.date_format_items.clone();
+        let results = self.search_entries(&self.search_query);
+
+        if results.len() == 0 {
+            ui.label("No entries match your search");
+        }
+
+        // A hidden private entry turning up would give away what it says
+        for entry in results.into_iter().filter(|entry| !entry.is_hidden(&self.revealed)) {
+            ui.heading(entry.date.format(&format).unwrap());
+            ui.label(highlight_matches(ui, &entry.content, &self.search_query));
+            ui.add_space(10.0);
+        }
+    }
+
+    fn stats_ui(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.selectable_value(&mut self.stats_range, StatsRange::Week, "This week");
+            ui.selectable_value(&mut self.stats_range, StatsRange::Month, "This month");
+        });
+
+        let start = match self.stats_range {
+            StatsRange::Week => self.week_start_of(self.curr_date),
+            StatsRange::Month => self.curr_date.replace_day(1).unwrap(),
+        };
+        let stats = self.stats_for_range(start, self.curr_date);
+
+        egui::Grid::new("stats").striped(true).show(ui, |ui| {
+            ui.label("");
+            ui.label("Min");
+            ui.label("Average");
+            ui.label("Max");
+            ui.end_row();
+
+            let rows = [
+                ("Weight", &stats.weight_kg, self.units.weight_factor(), self.units.weight_suffix()),
+                ("Waist", &stats.waist_cm, self.units.length_factor(), self.units.length_suffix()),
+            ];
+
+            for (name, measurement, factor, suffix) in rows {
+                ui.label(name);
+                if let Some(measurement) = measurement {
+                    ui.label(format!("{:.1}{}", measurement.min * factor, suffix));
+                    ui.label(format!("{:.1}{}", measurement.average * factor, suffix));
+                    ui.label(format!("{:.1}{}", measurement.max * factor, suffix));
+                } else {
+                    ui.label("--");
+                    ui.label("--");
+                    ui.label("--");
+                }
+                ui.end_row();
+            }
+        });
+
+        ui.label(format!("Days journaled: {}", stats.days_journaled));
+    }
+
+    fn calendar_ui(&mut self, ui: &mut egui::Ui) {
+        let (year, month) = shift_month(self.curr_date.year(), self.curr_date.month(), self.calendar_offset);
+        let first_day = Date::from_calendar_date(year, month, 1).unwrap();
+
+        // Browsing months doesn't change curr_date until a day is clicked
+        ui.horizontal(|ui| {
+            if ui.button("<").clicked() {
+                self.calendar_offset -= 1;
+            }
+            ui.label(format!("{} {}", month, year));
+            if ui.button(">").clicked() {
+                self.calendar_offset += 1;
+            }
+        });
+
+        egui::Grid::new("calendar").show(ui, |ui| {
+            for name in self.weekday_names(2) {
+                ui.label(RichText::new(name).weak());
+            }
+            ui.end_row();
+
+            for _ in 0..self.days_into_week(first_day) {
+                ui.label("");
+            }
+
+            for day in 1..=time::util::days_in_year_month(year, month) {
+                let date = Date::from_calendar_date(year, month, day).unwrap();
+
+                let has_entry = self.entries.iter().any(|entry| entry.date == date);
+
+                let mut day_text = RichText::new(format!("{}", day));
+                if has_entry {
+                    day_text = day_text.strong();
+                }
+                if date == self.curr_date {
+                    day_text = day_text.underline();
+                }
+
+                if ui.add(Label::new(day_text).sense(Sense::click())).clicked() {
+                    self.curr_date = date;
+                    self.calendar_offset = 0;
+
+                    // Days without an entry show the add prompt at the top instead
+                    if has_entry {
+                        self.scroll_to_date = Some(date);
+                    }
+                }
+
+                if date.weekday() == self.week_start.previous() {
+                    ui.end_row();
+                }
+            }
+        });
+    }
+
+    fn days_into_week(&self, date: Date) -> u8 {
+        (date.weekday().number_days_from_monday() + 7 - self.week_start.number_days_from_monday()) % 7
+    }
+
+    pub fn week_start_of(&self, date: Date) -> Date {
+        date.saturating_sub(time::Duration::days(self.days_into_week(date) as i64))
+    }
+
+    // Day names in calendar order, starting from week_start
+    fn weekday_names(&self, length: usize) -> Vec<String> {
+        let mut weekday = self.week_start;
+        let mut names = vec![];
+        for _ in 0..7 {
+            names.push(weekday.to_string().chars().take(length).collect());
+            weekday = weekday.next();
+        }
+
+        names
+    }
+
+    pub fn nearest_entry_date(&self, offset: f64) -> Option<Date> {
+        let target = self.curr_date.to_julian_day() as f64 + offset;
+
+        self.entries.iter()
+            .min_by(|a, b| {
+                let distance_a = (a.date.to_julian_day() as f64 - target).abs();
+                let distance_b = (b.date.to_julian_day() as f64 - target).abs();
+                distance_a.total_cmp(&distance_b)
+            })
+            .map(|entry| entry.date)
+    }
+
+    // Chart x values are day offsets from curr_date
+    fn jump_to_offset(&mut self, offset: f64) {
+        if let Some(date) = self.nearest_entry_date(offset) {
+            self.curr_date = date;
+            self.scroll_to_date = Some(date);
+            self.month_view = false;
+        }
+    }
+
+    fn go_to_today(&mut self) {
+        self.curr_date = self.today;
+        self.calendar_offset = 0;
+
+        // Without an entry the add prompt at the top is already in view
+        if self.entries.iter().any(|entry| entry.date == self.today) {
+            self.scroll_to_date = Some(self.today);
+        }
+    }
+
+    // Sets today's weight without going through the editor
+    pub fn log_weight(&mut self, weight_kg: f32) {
+        self.push_snapshot();
+
+        let weight_kg = weight_kg.clamp(0.0, MAX_WEIGHT_KG);
+        if let Some(entry) = self.entries.iter_mut().find(|entry| entry.date == self.today) {
+            entry.set_value(WEIGHT, weight_kg);
+        } else {
+            let mut new_entry = Entry::new(self.today);
+            new_entry.set_value(WEIGHT, weight_kg);
+
+            self.insert_entry(new_entry);
+        }
+    }
+
+    // In reading mode nothing can be clicked into edit
+    fn edit_sense(&self) -> Sense {
+        if self.read_only { Sense::hover() } else { Sense::click() }
+    }
+
+    // The day's prompt rotates through the list by date, then the template follows
+    pub fn starter_content(&self, date: Date) -> String {
+        let prompts: Vec<&str> = self.prompts.iter()
+            .map(|prompt| prompt.trim())
+            .filter(|prompt| prompt.len() > 0)
+            .collect();
+
+        let mut content = String::new();
+        if prompts.len() > 0 {
+            content.push_str(prompts[date.to_julian_day().rem_euclid(prompts.len() as i32) as usize]);
+        }
+        if self.entry_template.len() > 0 {
+            if content.len() > 0 {
+                content.push_str("\n\n");
+            }
+            content.push_str(&self.entry_template);
+        }
+
+        content
+    }
+
+    fn toggle_focus_mode(&mut self) {
+        if self.focus_mode {
+            // An untouched prompt or template doesn't count as writing anything
+            let starter = self.starter_content(self.today);
+            if let Some(entry) = self.entries.iter_mut().find(|entry| entry.date == self.today) {
+                if starter.trim().len() > 0 && entry.content.trim() == starter.trim() {
+                    entry.content.clear();
+                }
+            }
+
+            // Nothing written, no entry left behind
+            self.entries.retain(|entry| entry.edit || entry.has_data());
+            self.focus_mode = false;
+            return;
+        }
+
+        // The confirmation comes back through enter_focus_mode
+        if self.entries.iter().any(|entry| entry.date == self.today && entry.locked) {
+            self.pending_private_edit = Some(self.today);
+            self.private_edit_focus = true;
+            return;
+        }
+
+        self.enter_focus_mode();
+    }
+
+    fn enter_focus_mode(&mut self) {
+        self.push_snapshot();
+        if !self.entries.iter().any(|entry| entry.date == self.today) {
+            let mut new_entry = Entry::new(self.today);
+            new_entry.content = self.starter_content(self.today);
+
+            self.insert_entry(new_entry);
+        }
+
+        self.focus_mode = true;
+        self.first_time_edit = true;
+    }
+
+    // Only today's text, nothing else on screen
+    fn focus_ui(&mut self, ui: &mut egui::Ui) {
+        let format = self.date_format_items.clone();
+        let font = egui::FontId::proportional(self.content_font_size * 1.2);
+        let Some(entry) = self.entries.iter_mut().find(|entry| entry.date == self.today) else {
+            return;
+        };
+
+        ui.vertical_centered(|ui| {
+            ui.heading(entry.date.format(&format).unwrap());
+            ui.label(RichText::new("F11 or Esc to leave").weak());
+            ui.add_space(10.0);
+
+            let size = egui::vec2(ui.available_width().min(800.0), ui.available_height() - 20.0);
+            let response = ui.add_sized(size, TextEdit::multiline(&mut entry.content).font(font));
+
+            if self.first_time_edit {
+                response.request_focus();
+                self.first_time_edit = false;
+            }
+        });
+    }
+
+    // Opening a private entry asks first, every way into the editor goes through here
+    fn request_edit(&mut self, date: Date) {
+        if self.entries.iter().any(|entry| entry.date == date && entry.locked) {
+            self.pending_private_edit = Some(date);
+        } else {
+            self.edit_entry(date);
+        }
+    }
+
+    fn edit_entry(&mut self, date: Date) {
+        if let Some(entry) = self.entries.iter_mut().find(|entry| entry.date == date) {
+            entry.edit = true;
+        } else {
+            let mut new_entry = Entry::new(date);
+            new_entry.content = self.starter_content(date);
+            new_entry.edit = true;
+
+            self.insert_entry(new_entry);
+        }
+
+        self.curr_date = date;
+        self.scroll_to_date = Some(date);
+        self.month_view = false;
+        self.mode = Mode::Edit;
+        self.first_time_edit = true;
+    }
+
+    fn month_grid_ui(&mut self, ui: &mut egui::Ui) {
+        let year = self.curr_date.year();
+        let month = self.curr_date.month();
+        let first_day = Date::from_calendar_date(year, month, 1).unwrap();
+
+        let mut month_offset = 0;
+        ui.horizontal(|ui| {
+            if ui.button("<").clicked() {
+                month_offset = -1;
+            }
+            ui.heading(format!("{} {}", month, year));
+            if ui.button(">").clicked() {
+                month_offset = 1;
+            }
+        });
+
+        // Keep the same day when moving, clamped to the length of the new month
+        if month_offset != 0 {
+            let (year, month) = shift_month(year, month, month_offset);
+            let day = self.curr_date.day().min(time::util::days_in_year_month(year, month));
+            self.curr_date = Date::from_calendar_date(year, month, day).unwrap();
+            return;
+        }
+
+        let cell_size = egui::vec2(ui.available_width() / 7.0 - ui.spacing().item_spacing.x, 70.0);
+        let mut clicked_date = None;
+
+        egui::Grid::new("month_grid").show(ui, |ui| {
+            for name in self.weekday_names(usize::MAX) {
+                ui.label(RichText::new(name).weak());
+            }
+            ui.end_row();
+
+            for _ in 0..self.days_into_week(first_day) {
+                ui.label("");
+            }
+
+            for day in 1..=time::util::days_in_year_month(year, month) {
+                let date = Date::from_calendar_date(year, month, day).unwrap();
+                let entry = self.entries.iter().find(|entry| entry.date == date);
+
+                let cell = ui.allocate_ui(cell_size, |ui| {
+                    ui.set_min_size(cell_size);
+                    ui.set_max_width(cell_size.x);
+
+                    ui.horizontal(|ui| {
+                        let mut day_text = RichText::new(format!("{}", day)).strong();
+                        if date == self.today {
+                            day_text = day_text.underline();
+                        }
+                        ui.label(day_text);
+
+                        if let Some(entry) = entry {
+                            if entry.weight_kg() != 0.0 {
+                                ui.colored_label(color_from(self.weight_color), "●")
+                                    .on_hover_text(format!("{:.1}{}", entry.weight_kg() * self.units.weight_factor(), self.units.weight_suffix()));
+                            }
+                        }
+                    });
+
+                    if let Some(entry) = entry {
+                        let preview = if entry.is_hidden(&self.revealed) { "[locked]" } else { entry.content.lines().next().unwrap_or("") };
+                        ui.add(Label::new(RichText::new(preview).small()).truncate());
+                    }
+                });
+
+                if cell.response.interact(Sense::click()).clicked() {
+                    clicked_date = Some(date);
+                }
+
+                if date.weekday() == self.week_start.previous() {
+                    ui.end_row();
+                }
+            }
+        });
+
+        if let Some(date) = clicked_date {
+            if self.read_only {
+                self.curr_date = date;
+                self.month_view = false;
+            } else {
+                self.request_edit(date);
+            }
+        }
+    }
+
+    // Tasks only have a day once archived, so the previous day is the last archived one before date
+    pub fn duplicate_section_from(&mut self, date: Date, section_idx: usize) -> Option<(Date, usize)> {
+        let from = self.archived_tasks.iter()
+            .map(|(archived_date, _)| *archived_date)
+            .filter(|archived_date| *archived_date < date)
+            .max()?;
+        let texts: Vec<String> = self.archived_tasks.iter()
+            .filter(|(archived_date, _)| *archived_date == from)
+            .map(|(_, text)| text.clone())
+            .collect();
+
+        self.push_snapshot();
+
+        let section = self.sections.get_mut(section_idx)?;
+        for text in &texts {
+            // Archived notes were appended on a second line
+            let (text, note) = text.split_once('\n').unwrap_or((text.as_str(), ""));
+            section.add_task(text, false);
+            if let Some(task) = section.tasks.last_mut() {
+                task.note = note.to_string();
+            }
+        }
+
+        Some((from, texts.len()))
+    }
+
+    fn copy_tasks_ui(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            self.copy_target = self.copy_target.min(self.sections.len() - 1);
+
+            egui::ComboBox::from_id_salt("copy_target")
+                .selected_text(self.sections[self.copy_target].title.clone())
+                .show_ui(ui, |ui| {
+                    for (idx, section) in self.sections.iter().enumerate() {
+                        ui.selectable_value(&mut self.copy_target, idx, section.title.as_str());
+                    }
+                });
+
+            if ui.button("Copy yesterday's tasks").clicked() {
+                let format = self.date_format_items.clone();
+                self.copy_message = match self.duplicate_section_from(self.today, self.copy_target) {
+                    Some((from, num_tasks)) => format!("Copied {} tasks from {}", num_tasks, from.format(&format).unwrap()),
+                    None => String::from("No earlier tasks to copy"),
+                };
+            }
+        });
+
+        if !self.copy_message.is_empty() {
+            ui.label(&self.copy_message);
+        }
+    }
+
+    fn archive_ui(&self, ui: &mut egui::Ui) {
+        let format = self.date_format_items.clone();
+
+        let mut dates: Vec<Date> = self.archived_tasks.iter().map(|(date, _)| *date).collect();
+        dates.sort();
+        dates.dedup();
+
+        // Newest first
+        for date in dates.iter().rev() {
+            ui.label(RichText::new(date.format(&format).unwrap()).strong());
+
+            for (_, text) in self.archived_tasks.iter().filter(|(archived_date, _)| archived_date == date) {
+                ui.label(format!("✔ {}", text));
+            }
+        }
+    }
+
+    fn tag_results_ui(&mut self, ui: &mut egui::Ui, tag: &str) {
+        let format = self.date_format_items.clone();
+        let mut clicked_tag = None;
+
+        ui.horizontal(|ui| {
+            ui.label(format!("Showing entries tagged #{}", tag));
+            if ui.button("✖").clicked() {
+                clicked_tag = Some(tag.to_string());
+            }
+        });
+        ui.add_space(10.0);
+
+        for entry in self.entries_with_tag(tag) {
+            ui.heading(entry.date.format(&format).unwrap());
+
+            if let Some(tag) = tag_chips(ui, &entry.tags, Some(tag)) {
+                clicked_tag = Some(tag);
+            }
+
+            if entry.is_hidden(&self.revealed) {
+                ui.label(RichText::new("[locked]").weak());
+            } else if entry.content.len() > 0 {
+                ui.label(&entry.content);
+            }
+            ui.add_space(10.0);
+        }
+
+        if let Some(clicked_tag) = clicked_tag {
+            self.toggle_tag_filter(clicked_tag);
+        }
+    }
+
+    fn on_this_day_ui(&self, ui: &mut egui::Ui) {
+        egui::CollapsingHeader::new("On this day")
+            .default_open(true)
+            .show(ui, |ui| {
+                for entry in self.on_this_day(self.curr_date) {
+                    let years_ago = self.curr_date.year() - entry.date.year();
+                    let heading = if years_ago == 1 { String::from("1 year ago") } else { format!("{} years ago", years_ago) };
+
+                    ui.label(RichText::new(heading).strong());
+                    if entry.is_hidden(&self.revealed) {
+                        ui.label(RichText::new("[locked]").weak());
+                    } else {
+                        ui.label(&entry.content);
+                    }
+                    ui.add_space(5.0);
+                }
+            });
+        ui.separator();
+    }
+
+    // How much was logged on a day, 0 for nothing up to 3 for writing, measurements and more
+    fn activity_level(entry: &Entry) -> u8 {
+        let wrote = entry.content.len() > 0;
+        let measured = entry.weight_kg() != 0.0 || entry.waist_cm() != 0.0;
+        let tracked = entry.mood.is_some() || entry.workouts.len() > 0
+            || entry.values.keys().any(|name| name != WEIGHT && name != WAIST);
+
+        wrote as u8 + measured as u8 + tracked as u8
+    }
+
+    // The last year as 53 week columns of 7 days, painted by hand
+    fn heatmap_ui(&mut self, ui: &mut egui::Ui) {
+        let levels: BTreeMap<i32, u8> = self.entries.iter()
+            .map(|entry| (entry.date.to_julian_day(), MyApp::activity_level(entry)))
+            .collect();
+
+        let first_day = self.week_start_of(self.today).to_julian_day() - 52 * 7;
+        let today = self.today.to_julian_day();
+
+        let cell = (ui.available_width() / 53.0).min(14.0);
+        let (rect, response) = ui.allocate_exact_size(egui::vec2(cell * 53.0, cell * 7.0), Sense::click());
+        let painter = ui.painter_at(rect);
+
+        let empty = ui.visuals().faint_bg_color;
+        let cell_rect = |julian: i32| {
+            let (week, day) = ((julian - first_day) / 7, (julian - first_day) % 7);
+            egui::Rect::from_min_size(rect.min + egui::vec2(week as f32 * cell, day as f32 * cell), egui::vec2(cell, cell)).shrink(1.0)
+        };
+
+        for julian in first_day..=today {
+            let color = match levels.get(&julian).copied().unwrap_or(0) {
+                0 => empty,
+                level => Color32::from_rgb(0, 200, 80).gamma_multiply(0.25 + 0.25 * level as f32),
+            };
+            painter.rect_filled(cell_rect(julian), 2.0, color);
+        }
+
+        // Cells map back to a day from where the pointer is
+        let hovered_day = response.hover_pos().map(|pos| {
+            let week = ((pos.x - rect.left()) / cell).floor() as i32;
+            let day = ((pos.y - rect.top()) / cell).floor() as i32;
+            first_day + week.clamp(0, 52) * 7 + day.clamp(0, 6)
+        }).filter(|julian| *julian <= today);
+
+        if let Some(date) = hovered_day.and_then(|julian| Date::from_julian_day(julian).ok()) {
+            let format = self.date_format_items.clone();
+            let response = response.on_hover_text(date.format(&format).unwrap());
+
+            if response.clicked() {
+                self.curr_date = date;
+                self.calendar_offset = 0;
+                if levels.contains_key(&date.to_julian_day()) {
+                    self.scroll_to_date = Some(date);
+                }
+            }
+        }
+    }
+
+    // The 7 days up to and including end
+    pub fn weekly_review(&self, end: Date) -> WeeklyReview {
+        let start = end.saturating_sub(time::Duration::days(6));
+        let mut week: Vec<&Entry> = self.entries.iter()
+            .filter(|entry| entry.date >= start && entry.date <= end)
+            .collect();
+        week.sort_by_key(|entry| entry.date);
+
+        // Private entries only show that they exist
+        let days = week.iter()
+            .filter(|entry| entry.has_data())
+            .map(|entry| {
+                let snippet = if entry.is_hidden(&self.revealed) { String::from("[locked]") } else { entry.content.lines().next().unwrap_or("").to_string() };
+                (entry.date, snippet)
+            })
+            .collect();
+
+        let weights: Vec<f32> = week.iter()
+            .map(|entry| entry.weight_kg())
+            .filter(|weight_kg| *weight_kg != 0.0)
+            .collect();
+        let average_weight = if weights.len() > 0 { Some(weights.iter().sum::<f32>() / weights.len() as f32) } else { None };
+
+        // Finished tasks are archived on the day they get cleaned
+        let archived = self.archived_tasks.iter()
+            .filter(|(date, _)| *date >= start && *date <= end)
+            .count();
+        let tasks = self.sections.iter().flat_map(|section| section.tasks.iter());
+        let (done, open): (Vec<&Task>, Vec<&Task>) = tasks.partition(|task| task.done);
+
+        WeeklyReview {
+            start,
+            end,
+            days,
+            average_weight,
+            min_weight: weights.iter().copied().reduce(f32::min),
+            max_weight: weights.iter().copied().reduce(f32::max),
+            tasks_done: archived + done.len(),
+            tasks_open: open.len(),
+        }
+    }
+
+    // Files dropped on the window go to the importer matching their extension
+    fn import_dropped_file(&mut self, path: &Path) -> String {
+        let extension = path.extension().and_then(|extension| extension.to_str()).unwrap_or("").to_lowercase();
+        let path_string = path.display().to_string();
+
+        // Same messages as the settings buttons, skipped rows are listed there
+        match extension.as_str() {
+            "csv" if is_health_csv(path) => {
+                let format = self.date_format_items.clone();
+                self.import_message = match self.import_health_csv(&path_string) {
+                    Ok((num_days, Some((first, last)))) => format!(
+                        "Imported {} days of weights from {} to {}",
+                        num_days, first.format(&format).unwrap(), last.format(&format).unwrap()),
+                    Ok((_, None)) => String::from("No weights found to import"),
+                    Err(error) => format!("Import failed: {}", error),
+                };
+                self.import_message.clone()
+            },
+            "csv" => {
+                self.import_message = match self.import_csv(&path_string) {
+                    Ok(num_imported) => format!("Imported {} entries", num_imported),
+                    Err(error) => format!("Import failed: {}", error),
+                };
+                self.import_message.clone()
+            },
+            // Replace or merge is asked first, the result shows up once answered
+            "json" => {
+                self.pending_import = Some(path_string);
+                String::new()
+            },
+            _ => format!("Can't import {} files, drop a .csv or .json", if extension.is_empty() { "these" } else { extension.as_str() }),
+        }
+    }
+
+    fn weekly_review_ui(&mut self, ui: &mut egui::Ui) {
+        let review = self.weekly_review(self.curr_date);
+        let format = self.date_format_items.clone();
+
+        ui.heading(format!("{} – {}", review.start.format(&format).unwrap(), review.end.format(&format).unwrap()));
+        ui.add_space(5.0);
+
+        if review.days.len() == 0 {
+            ui.label("Nothing logged this week");
+        }
+        let mut clicked_date = None;
+        for (date, snippet) in &review.days {
+            ui.horizontal(|ui| {
+                if ui.add(Label::new(RichText::new(date.format(&format).unwrap()).strong()).sense(Sense::click())).clicked() {
+                    clicked_date = Some(*date);
+                }
+                ui.add(Label::new(snippet).truncate());
+            });
+        }
+        ui.separator();
+
+        let weight = |weight_kg: Option<f32>| match weight_kg {
+            Some(weight_kg) => format!("{:.1}{}", weight_kg * self.units.weight_factor(), self.units.weight_suffix()),
+            None => String::from("--"),
+        };
+        egui::Grid::new("review_weights").show(ui, |ui| {
+            ui.label("Average weight");
+            ui.label(weight(review.average_weight));
+            ui.end_row();
+            ui.label("Lowest");
+            ui.label(weight(review.min_weight));
+            ui.end_row();
+            ui.label("Highest");
+            ui.label(weight(review.max_weight));
+            ui.end_row();
+        });
+        ui.separator();
+
+        ui.label(format!("✔ {} tasks done, {} still open", review.tasks_done, review.tasks_open));
+
+        if let Some(date) = clicked_date {
+            self.curr_date = date;
+            self.scroll_to_date = Some(date);
+        }
+    }
+
+    fn pinned_ui(&mut self, ui: &mut egui::Ui) {
+        let format = self.date_format_items.clone();
+        let mut jump_date = None;
+
+        ui.label(RichText::new("📌 Pinned").strong());
+        for entry in self.pinned_entries() {
+            ui.horizontal(|ui| {
+                if ui.add(Label::new(RichText::new(entry.date.format(&format).unwrap()).strong()).sense(Sense::click())).clicked() {
+                    jump_date = Some(entry.date);
+                }
+                if entry.is_hidden(&self.revealed) {
+                    ui.label(RichText::new("[locked]").weak());
+                } else {
+                    ui.add(Label::new(entry.content.lines().next().unwrap_or("")).truncate());
+                }
+            });
+        }
+        ui.separator();
+
+        if let Some(date) = jump_date {
+            self.curr_date = date;
+            self.scroll_to_date = Some(date);
+        }
+    }
+
+    fn toggle_tag_filter(&mut self, tag: String) {
+        if self.tag_filter.as_ref() == Some(&tag) {
+            self.tag_filter = None;
+        } else {
+            self.tag_filter = Some(tag);
+        }
+    }
+
+    fn profile_selector_ui(&mut self, ui: &mut egui::Ui) {
+        let mut switch_to = None;
+        egui::ComboBox::from_id_salt("profile")
+            .selected_text(&self.active_profile)
+            .show_ui(ui, |ui| {
+                for profile in &self.profiles {
+                    if ui.selectable_label(*profile == self.active_profile, profile).clicked() {
+                        switch_to = Some(profile.clone());
+                    }
+                }
+            });
+
+        if let Some(profile) = switch_to {
+            self.save_error = self.switch_profile(&profile).err().map(|error| error.to_string());
+        }
+    }
+
+    fn update_window_geometry(&mut self, ctx: &egui::Context) {
+        let (inner_rect, outer_rect, monitor_size) = ctx.input(|i| {
+            let viewport = i.viewport();
+            (viewport.inner_rect, viewport.outer_rect, viewport.monitor_size)
+        });
+
+        // The monitor size is only known once the window is up, so restore on the first frame
+        if !self.window_restored {
+            self.window_restored = true;
+
+            if let Some([width, height]) = self.window_size {
+                ctx.send_viewport_cmd(egui::ViewportCommand::InnerSize(egui::vec2(width, height)));
+            }
+
+            // A position from a monitor that isn't there any more would leave the window off-screen
+            if let (Some([x, y]), Some(monitor_size)) = (self.window_pos, monitor_size) {
+                if x >= 0.0 && y >= 0.0 && x < monitor_size.x - 50.0 && y < monitor_size.y - 50.0 {
+                    ctx.send_viewport_cmd(egui::ViewportCommand::OuterPosition(egui::pos2(x, y)));
+                }
+            }
+
+            return;
+        }
+
+        if let Some(inner_rect) = inner_rect {
+            self.window_size = Some([inner_rect.width(), inner_rect.height()]);
+        }
+        if let Some(outer_rect) = outer_rect {
+            self.window_pos = Some([outer_rect.min.x, outer_rect.min.y]);
+        }
+    }
+
+    fn password_dialog_ui(&mut self, ui: &mut egui::Ui) {
+        ui.vertical_centered(|ui| {
+            ui.add_space(ui.available_height() / 3.0);
+            if self.profiles.len() > 1 {
+                self.profile_selector_ui(ui);
+            }
+            ui.heading("This diary is encrypted");
+            ui.add_space(10.0);
+
+            let response = ui.add(TextEdit::singleline(&mut self.password)
+                .password(true)
+                .hint_text("Password"));
+
+            if ui.memory(|m| m.focused().is_none()) {
+                response.request_focus();
+            }
+
+            let submitted = response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+            if ui.button("Unlock").clicked() || submitted {
+                if let Err(error) = self.unlock() {
+                    self.unlock_error = Some(error.to_string());
+                    self.password.clear();
+                }
+            }
+
+            if let Some(error) = &self.unlock_error {
+                ui.colored_label(Color32::RED, error);
+            }
+        });
+    }
+
+    fn replace_ui(&mut self, ui: &mut egui::Ui) {
+        egui::Grid::new("replace").show(ui, |ui| {
+            ui.label("Find");
+            ui.add(TextEdit::singleline(&mut self.replace_find));
+            ui.end_row();
+
+            ui.label("Replace with");
+            ui.add(TextEdit::singleline(&mut self.replace_with));
+            ui.end_row();
+        });
+
+        ui.checkbox(&mut self.replace_ignore_case, "Ignore case");
+
+        // Counting scans every entry, so only redo it when the search changes
+        let num_matches = match &self.replace_count {
+            Some((find, ignore_case, num_matches)) if *find == self.replace_find && *ignore_case == self.replace_ignore_case => *num_matches,
+            _ => {
+                let num_matches = self.count_replace_matches(&self.replace_find, self.replace_ignore_case);
+                self.replace_count = Some((self.replace_find.clone(), self.replace_ignore_case, num_matches));
+                num_matches
+            },
+        };
+        ui.label(format!("{} entries match", num_matches));
+
+        if ui.add_enabled(num_matches > 0, egui::Button::new("Replace all")).clicked() {
+            let find = self.replace_find.clone();
+            let replace = self.replace_with.clone();
+            let num_changed = self.replace_in_entries(&find, &replace, self.replace_ignore_case);
+            self.replace_message = format!("Replaced in {} entries, Ctrl+Z to undo", num_changed);
+            self.replace_count = None;
+        }
+
+        if !self.replace_message.is_empty() {
+            ui.label(&self.replace_message);
+        }
+    }
+
+    fn settings_ui(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.label("Diary file");
+            ui.add(TextEdit::singleline(&mut self.path_to_file));
+
+            if ui.button("Save now").clicked() {
+                self.save_error = self.save_to_file().err().map(|error| error.to_string());
+            }
+        });
+
+        if let Some(error) = &self.save_error {
+            ui.colored_label(Color32::RED, error);
+        }
+
+        ui.horizontal(|ui| {
+            ui.label("New profile");
+            ui.add(TextEdit::singleline(&mut self.new_profile).hint_text("name"));
+
+            if ui.button("Add").clicked() {
+                self.save_error = self.add_profile().err().map(|error| error.to_string());
+            }
+        });
+
+        ui.horizontal(|ui| {
+            ui.label("Theme");
+            let mut changed = ui.radio_value(&mut self.theme, Theme::Dark, "Dark").changed();
+            changed |= ui.radio_value(&mut self.theme, Theme::Light, "Light").changed();
+            changed |= ui.radio_value(&mut self.theme, Theme::System, "System").changed();
+
+            if changed {
+                ui.ctx().set_theme(self.theme.preference());
+            }
+        });
+
+        ui.horizontal(|ui| {
+            ui.label("Weight line");
+            ui.color_edit_button_srgb(&mut self.weight_color);
+            ui.label("Waist line");
+            ui.color_edit_button_srgb(&mut self.waist_color);
+        });
+
+        ui.checkbox(&mut self.render_markdown, "Render entries as Markdown");
+
+        ui.label("Custom metrics");
+        let mut removed = None;
+        for (idx, metric) in self.metrics.iter_mut().enumerate() {
+            ui.horizontal(|ui| {
+                ui.label(&metric.name);
+                ui.add(TextEdit::singleline(&mut metric.unit).desired_width(50.0).hint_text("unit"));
+                ui.color_edit_button_srgb(&mut metric.color);
+                if ui.button("-").clicked() {
+                    removed = Some(idx);
+                }
+            });
+        }
+        // Its values go too, otherwise entries holding only those would be kept but never shown
+        if let Some(idx) = removed {
+            self.push_snapshot();
+            let metric = self.metrics.remove(idx);
+            for entry in &mut self.entries {
+                entry.values.remove(&metric.name);
+            }
+            self.entries.retain(|entry| entry.has_data());
+        }
+
+        ui.horizontal(|ui| {
+            ui.add(TextEdit::singleline(&mut self.new_metric).hint_text("Metric name"));
+
+            let name = self.new_metric.trim().to_string();
+            let valid = name.len() > 0 && !self.metrics.iter().any(|metric| metric.name == name)
+                && !BUILT_IN_METRICS.iter().any(|built_in| built_in.eq_ignore_ascii_case(&name));
+            if ui.add_enabled(valid, egui::Button::new("Add metric")).clicked() {
+                self.metrics.push(MetricDef {name, unit: String::new(), color: [255, 128, 0]});
+                self.new_metric.clear();
+            }
+        });
+
+        // One prompt per line, empty lines are skipped when picking
+        ui.label("Journaling prompts");
+        let mut prompts_text = self.prompts.join("\n");
+        if ui.add(TextEdit::multiline(&mut prompts_text)
+            .desired_rows(3)
+            .hint_text("What am I grateful for?")).changed() {
+            self.prompts = prompts_text.split('\n').map(String::from).collect();
+        }
+
+        ui.label("New entry template");
+        ui.add(TextEdit::multiline(&mut self.entry_template)
+            .desired_rows(3)
+            .hint_text("Workout:\nMeals:\nNotes:"));
+
+        ui.horizontal(|ui| {
+            ui.label("Date format");
+            if ui.add(TextEdit::singleline(&mut self.date_format).hint_text("[day]-[month]-[year]")).changed() {
+                self.apply_date_format();
+            }
+        });
+
+        if let Some(error) = &self.date_format_error {
+            ui.colored_label(Color32::RED, error);
+        }
+
+        ui.horizontal(|ui| {
+            ui.label("Week starts on");
+            egui::ComboBox::from_id_salt("week_start")
+                .selected_text(self.week_start.to_string())
+                .show_ui(ui, |ui| {
+                    for weekday in [Weekday::Monday, Weekday::Saturday, Weekday::Sunday] {
+                        ui.selectable_value(&mut self.week_start, weekday, weekday.to_string());
+                    }
+                });
+        });
+
+        ui.horizontal(|ui| {
+            ui.label("Units");
+            ui.radio_value(&mut self.units, Units::Metric, "Metric");
+            ui.radio_value(&mut self.units, Units::Imperial, "Imperial");
+        });
+
+        ui.horizontal(|ui| {
+            ui.label("Height");
+
+            let length_factor = self.units.length_factor();
+            let mut height = self.height_cm * length_factor;
+            if ui.add(DragValue::new(&mut height).speed(0.5).range(0.0..=250.0 * length_factor).suffix(self.units.length_suffix())).changed() {
+                self.height_cm = height / length_factor;
+            }
+        });
+
+        ui.horizontal(|ui| {
+            ui.label("Password");
+            ui.add(TextEdit::singleline(&mut self.password).password(true));
+        });
+        ui.add_enabled(self.password.len() > 0 || self.encrypted, egui::Checkbox::new(&mut self.encrypted, "Encrypt diary file"));
+
+        ui.horizontal(|ui| {
+            ui.label("Backups to keep");
+            ui.add(DragValue::new(&mut self.backup_count).range(0..=20));
+        });
+
+        ui.horizontal(|ui| {
+            ui.label("Remind me after");
+            ui.add(DragValue::new(&mut self.reminder_days).range(1..=365).suffix(" days"));
+        });
+
+        ui.horizontal(|ui| {
+            let mut has_reminder = self.reminder_hour.is_some();
+            if ui.checkbox(&mut has_reminder, "Weigh-in reminder at").changed() {
+                self.reminder_hour = if has_reminder { Some(8) } else { None };
+            }
+
+            if let Some(reminder_hour) = &mut self.reminder_hour {
+                ui.add(DragValue::new(reminder_hour).range(0..=23).suffix(":00"));
+            }
+        });
+
+        ui.horizontal(|ui| {
+            ui.label("Chart image width");
+            ui.add(DragValue::new(&mut self.chart_export_width).range(200..=4000).suffix(" px"));
+        });
+
+        ui.horizontal(|ui| {
+            ui.label("Auto-save every");
+            ui.add(DragValue::new(&mut self.autosave_secs).range(5..=3600).suffix(" s"));
+        });
+
+        ui.separator();
+
+        ui.horizontal(|ui| {
+            let mut has_goal = self.goal_weight_kg.is_some();
+            if ui.checkbox(&mut has_goal, "Goal weight").changed() {
+                if has_goal {
+                    self.goal_weight_kg = Some(self.latest_weight().unwrap_or(70.0));
+                } else {
+                    self.goal_weight_kg = None;
+                }
+            }
+
+            if let Some(goal_weight_kg) = &mut self.goal_weight_kg {
+                let weight_factor = self.units.weight_factor();
+                let mut goal_weight = *goal_weight_kg * weight_factor;
+                if ui.add(DragValue::new(&mut goal_weight).speed(0.1).suffix(self.units.weight_suffix())).changed() {
+                    *goal_weight_kg = goal_weight / weight_factor;
+                }
+            }
+        });
+
+        ui.horizontal(|ui| {
+            let mut has_goal = self.calorie_goal.is_some();
+            if ui.checkbox(&mut has_goal, "Daily calorie target").changed() {
+                self.calorie_goal = if has_goal { Some(2000) } else { None };
+            }
+
+            if let Some(calorie_goal) = &mut self.calorie_goal {
+                ui.add(DragValue::new(calorie_goal).speed(10).range(500..=10000).suffix(" kcal"));
+            }
+        });
+
+        ui.separator();
+
+        ui.horizontal(|ui| {
+            ui.add(TextEdit::singleline(&mut self.import_path).hint_text("entries.csv"));
+
+            if ui.add_enabled(!self.read_only, egui::Button::new("Import CSV")).clicked() {
+                let path = self.import_path.clone();
+                match self.import_csv(&path) {
+                    Ok(num_imported) => self.import_message = format!("Imported {} entries", num_imported),
+                    Err(error) => self.import_message = format!("Import failed: {}", error),
+                }
+            }
+
+            if ui.add_enabled(!self.read_only, egui::Button::new("Import health CSV")).clicked() {
+                let path = self.import_path.clone();
+                let format = self.date_format_items.clone();
+                self.import_message = match self.import_health_csv(&path) {
+                    Ok((num_days, Some((first, last)))) => format!(
+                        "Imported {} days of weights from {} to {}",
+                        num_days, first.format(&format).unwrap(), last.format(&format).unwrap()),
+                    Ok((_, None)) => String::from("No weights found to import"),
+                    Err(error) => format!("Import failed: {}", error),
+                };
+            }
+        });
+
+        if !self.import_message.is_empty() {
+            ui.label(&self.import_message);
+        }
+        for error in &self.import_errors {
+            ui.colored_label(Color32::RED, error);
+        }
+
+        ui.horizontal(|ui| {
+            ui.add(TextEdit::singleline(&mut self.export_path).hint_text("diary.md"));
+
+            if ui.button("Export Markdown").clicked() {
+                match self.export_markdown(&self.export_path) {
+                    Ok(()) => self.export_message = format!("Exported to {}", self.export_path),
+                    Err(error) => self.export_message = format!("Export failed: {}", error),
+                }
+            }
+        });
+
+        if !self.export_message.is_empty() {
+            ui.label(&self.export_message);
+        }
+
+        ui.horizontal(|ui| {
+            ui.add(TextEdit::singleline(&mut self.backup_path).hint_text("diary-backup.json"));
+
+            // The export isn't encrypted, so an encrypted diary asks first
+            if ui.button("Export all").clicked() {
+                if self.encrypted {
+                    self.confirm_plain_export = true;
+                } else {
+                    self.export_backup();
+                }
+            }
+            // Replace or merge is asked in a modal
+            if ui.add_enabled(!self.read_only, egui::Button::new("Import all")).clicked() {
+                self.pending_import = Some(self.backup_path.clone());
+            }
+        });
+
+        if !self.backup_message.is_empty() {
+            ui.label(&self.backup_message);
+        }
+    }
+
+    pub fn confirm_delete(&mut self, pending_delete: PendingDelete) {
+        match pending_delete {
+            PendingDelete::Entry(date) => {
+                self.entries.retain(|entry| entry.date != date);
+            },
+            PendingDelete::Section(section) => {
+                if section < self.sections.len() {
+                    self.sections.remove(section);
+                }
+            },
+            PendingDelete::Task(section, task) => {
+                if let Some(section) = self.sections.get_mut(section) {
+                    if task < section.tasks.len() {
+                        section.tasks.remove(task);
+                    }
+                }
+            },
+            PendingDelete::CompletedTasks { .. } => {
+                self.clean_tasks();
+            },
+        }
+
+        self.mode = Mode::Main;
+    }
+
+    pub fn duplicate_dates(&self) -> Vec<Date> {
+        let mut seen = HashSet::new();
+        let mut duplicates: Vec<Date> = self.entries.iter()
+            .map(|entry| entry.date)
+            .filter(|date| !seen.insert(*date))
+            .collect();
+        duplicates.sort();
+        duplicates.dedup();
+
+        duplicates
+    }
+
+    // Folds every entry sharing a date into the first one, measurements keep the largest value
+    pub fn merge_duplicates(&mut self) {
+        self.push_snapshot();
+
+        let mut merged: Vec<Entry> = vec![];
+        for entry in std::mem::take(&mut self.entries) {
+            let Some(first) = merged.iter_mut().find(|other| other.date == entry.date) else {
+                merged.push(entry);
+                continue;
+            };
+
+            if entry.content.len() > 0 {
+                if first.content.len() > 0 {
+                    first.content.push_str("\n\n");
+                }
+                first.content.push_str(&entry.content);
+            }
+            first.mood = first.mood.max(entry.mood);
+            for (name, value) in entry.values {
+                let merged = first.value(&name).map_or(value, |other| other.max(value));
+                first.values.insert(name, merged);
+            }
+            first.pinned |= entry.pinned;
+            first.locked |= entry.locked;
+            for tag in entry.tags {
+                if !first.tags.contains(&tag) {
+                    first.tags.push(tag);
+                }
+            }
+            first.workouts.extend(entry.workouts);
+            first.image_paths.extend(entry.image_paths);
+        }
+
+        self.entries = merged;
+        self.sort_entries();
+    }
+
+    pub fn sort_entries(&mut self) {
+        // Newest first, the same order insert_entry keeps
+        self.entries.sort_by(|a, b| b.date.cmp(&a.date));
+    }
+
+    pub fn insert_entry(&mut self, entry: Entry) {
+        // Entries are kept newest first
+        let index = self.entries.iter().position(|other| other.date < entry.date).unwrap_or(self.entries.len());
+        self.entries.insert(index, entry);
+    }
+
+    pub fn add_section(&mut self, title: &str, edit: bool) {
+        self.sections.push(Section {title: title.to_string(), tasks: vec![], edit, collapsed: false, due: None});
+    }
+
+    pub fn overdue_sections(&self) -> Vec<&Section> {
+        self.sections.iter()
+            .filter(|section| section.due.is_some_and(|due| due < self.curr_date))
+            .collect()
+    }
+
+    pub fn section_progress(&self, idx: usize) -> (usize, usize) {
+        match self.sections.get(idx) {
+            Some(section) => (section.tasks.iter().filter(|task| task.done).count(), section.tasks.len()),
+            None => (0, 0),
+        }
+    }
+
+    pub fn move_task(&mut self, section: usize, from: usize, to: usize) {
+        if let Some(section) = self.sections.get_mut(section) {
+            if from < section.tasks.len() && to < section.tasks.len() {
+                let task = section.tasks.remove(from);
+                section.tasks.insert(to, task);
+            }
+        }
+    }
+
+    pub fn move_section(&mut self, from: usize, to: usize) {
+        if from < self.sections.len() && to < self.sections.len() {
+            let section = self.sections.remove(from);
+            self.sections.insert(to, section);
+        }
+    }
+
+    pub fn sort_tasks_by_priority(&mut self) {
+        for section in &mut self.sections {
+            // Stable sort, so tasks with the same priority keep their order
+            section.tasks.sort_by(|a, b| b.priority.cmp(&a.priority));
+        }
+    }
+
+    // Past the reminder hour and no weight for today yet
+    pub fn check_weigh_in(&mut self) {
+        let hour = OffsetDateTime::now_local().unwrap_or_else(|_| OffsetDateTime::now_utc()).hour();
+        let weighed_in = self.entries.iter().any(|entry| entry.date == self.today && entry.weight_kg() != 0.0);
+
+        self.weigh_in_due = self.reminder_hour.is_some_and(|reminder_hour| hour >= reminder_hour) && !weighed_in;
+    }
+
+    // Handles the day changing, either while running or since the app was last open
+    pub fn check_date(&mut self) {
+        // A locked diary has no sections loaded, unlock catches up instead
+        if self.locked {
+            return;
+        }
+
+        let today = default_today();
+
+        if today > self.today {
+            self.roll_over_tasks(today);
+            self.today = today;
+            self.curr_date = today;
+        }
+    }
+
+    pub fn roll_over_tasks(&mut self, new_date: Date) {
+        let prev_date = self.today;
+
+        for section in &mut self.sections {
+            for task in &mut section.tasks {
+                if let Some(recurrence) = task.recurrence {
+                    if task.done && recurrence.is_due(prev_date, new_date) {
+                        task.done = false;
+                        for (_, done) in &mut task.subtasks {
+                            *done = false;
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn push_snapshot(&mut self) {
+        self.undo_stack.push(Snapshot {
+            entries: self.entries.clone(),
+            sections: self.sections.clone(),
+            archived_tasks: self.archived_tasks.clone(),
+        });
+
+        if self.undo_stack.len() > MAX_UNDO {
+            self.undo_stack.remove(0);
+        }
+    }
+
+    pub fn undo(&mut self) {
+        if let Some(snapshot) = self.undo_stack.pop() {
+            self.entries = snapshot.entries;
+            self.sections = snapshot.sections;
+            self.archived_tasks = snapshot.archived_tasks;
+
+            // The snapshot is taken on the frame an edit starts, so drop anything
+            // that was only just added and clear the edit flags
+            self.entries.retain(|t| !t.edit || t.has_data());
+            for entry in &mut self.entries {
+                entry.edit = false;
+            }
+
+            self.sections.retain(|t| !t.edit || t.title.len() > 0 || t.tasks.len() > 0);
+            for section in &mut self.sections {
+                section.edit = false;
+                section.tasks.retain(|t| !t.edit || t.text.len() > 0);
+                for task in &mut section.tasks {
+                    task.edit = false;
+                }
+            }
+
+            self.mode = Mode::Main;
+        }
+    }
+
+    // Only counts what clean_tasks removes, recurring tasks stay
+    pub fn completed_task_count(&self) -> usize {
+        self.sections.iter()
+            .flat_map(|section| section.tasks.iter())
+            .filter(|task| task.is_complete() && task.recurrence.is_none())
+            .count()
+    }
+
+    pub fn emptied_section_count(&self) -> usize {
+        self.sections.iter()
+            .filter(|section| section.tasks.iter().all(|task| task.is_complete() && task.recurrence.is_none()))
+            .count()
+    }
+
+    pub fn clean_tasks(&mut self) {
+        self.push_snapshot();
+
+        for section in &mut self.sections {
+            for task in &section.tasks {
+                if task.is_complete() && task.recurrence.is_none() {
+                    // The archive only keeps text, so the note goes on a second line
+                    if task.note.is_empty() {
+                        self.archived_tasks.push((self.today, task.text.clone()));
+                    } else {
+                        self.archived_tasks.push((self.today, format!("{}\n{}", task.text, task.note)));
+                    }
+                }
+            }
+
+            // Recurring tasks are never cleaned, they get reset instead
+            section.tasks.retain(|t| !t.is_complete() || t.recurrence.is_some());
+        }
+
+        self.sections.retain(|section| section.tasks.len() > 0);
+    }
+}
+
+// Diary CSVs start with a date or a bare "date" header, health exports name their columns
+fn is_health_csv(path: &Path) -> bool {
+    let Ok(data) = fs::read_to_string(path) else {
+        return false;
+    };
+    let first_line = data.lines().next().unwrap_or("").to_lowercase();
+    let first_field = first_line.split(',').next().unwrap_or("").trim().trim_matches('"').to_string();
+
+    let format = format_description::parse("[year]-[month]-[day]").unwrap();
+    let starts_with_date = first_field.get(..10).is_some_and(|date_field| Date::parse(date_field, &format).is_ok());
+
+    first_field != "date" && !starts_with_date && first_line.contains("weight")
+}
+
+fn fill_missing(value: &mut serde_json::Value, key: &str, default: serde_json::Value) {
+    if let Some(object) = value.as_object_mut() {
+        object.entry(key).or_insert(default);
+    }
+}
+
+// Brings a saved app up to the current layout before serde sees it
+fn migrate(mut value: serde_json::Value) -> serde_json::Value {
+    let version = value.get("schema_version").and_then(|version| version.as_u64()).unwrap_or(0);
+
+    if version < 1 {
+        // Unversioned files could be missing the flags that have no serde default
+        fill_missing(&mut value, "first_time_edit", false.into());
+        fill_missing(&mut value, "redux_mode", false.into());
+        fill_missing(&mut value, "scale_factor", 2.0.into());
+        fill_missing(&mut value, "path_to_file", "diary.json".into());
+
+        if let Some(sections) = value.get_mut("sections").and_then(|sections| sections.as_array_mut()) {
+            for section in sections {
+                fill_missing(section, "edit", false.into());
+
+                if let Some(tasks) = section.get_mut("tasks").and_then(|tasks| tasks.as_array_mut()) {
+                    for task in tasks {
+                        fill_missing(task, "done", false.into());
+                        fill_missing(task, "edit", false.into());
+                    }
+                }
+            }
+        }
+    }
+
+    if version < 2 {
+        if let Some(entries) = value.get_mut("entries").and_then(|entries| entries.as_array_mut()) {
+            for entry in entries {
+                move_readings_into_values(entry);
+            }
+        }
+    }
+
+    if let Some(object) = value.as_object_mut() {
+        object.insert(String::from("schema_version"), SCHEMA_VERSION.into());
+    }
+
+    value
+}
+
+// Schema 2 keeps every reading in Entry::values, zero and null meant nothing was logged
+fn move_readings_into_values(entry: &mut serde_json::Value) {
+    let Some(object) = entry.as_object_mut() else {
+        return;
+    };
+
+    let mut values = match object.remove("values") {
+        Some(serde_json::Value::Object(values)) => values,
+        _ => serde_json::Map::new(),
+    };
+
+    let fields = [
+        ("weight_kg", WEIGHT),
+        ("waist_cm", WAIST),
+        ("sleep_hours", SLEEP),
+        ("resting_hr", RESTING_HR),
+        ("calories", CALORIES),
+        ("body_fat_pct", BODY_FAT),
+    ];
+    for (field, name) in fields {
+        if let Some(reading) = object.remove(field).and_then(|reading| reading.as_f64()) {
+            if reading != 0.0 {
+                values.insert(name.to_string(), reading.into());
+            }
+        }
+    }
+
+    object.insert(String::from("values"), serde_json::Value::Object(values));
+}
+
+fn load_json(data: &[u8]) -> serde_json::Result<MyApp> {
+    let value: serde_json::Value = serde_json::from_slice(data)?;
+
+    serde_json::from_value(migrate(value))
+}
+
+fn store_json(storage: &mut dyn eframe::Storage, app: &MyApp) {
+    if let Ok(json) = serde_json::to_string(app) {
+        storage.set_string(eframe::APP_KEY, json);
+    }
+}
+
+fn parse_tags(text: &str) -> Vec<String> {
+    let mut tags: Vec<String> = vec![];
+
+    for tag in text.split(',').map(|tag| tag.trim()) {
+        if tag.len() > 0 && !tags.iter().any(|other| other == tag) {
+            tags.push(tag.to_string());
+        }
+    }
+
+    tags
+}
+
+// Renders the tags as small chips, returns the one that was clicked if any
+fn tag_chips(ui: &mut egui::Ui, tags: &[String], selected: Option<&str>) -> Option<String> {
+    let mut clicked_tag = None;
+
+    if tags.len() > 0 {
+        ui.horizontal_wrapped(|ui| {
+            for tag in tags {
+                if ui.selectable_label(selected == Some(tag.as_str()), RichText::new(format!("#{}", tag)).small()).clicked() {
+                    clicked_tag = Some(tag.clone());
+                }
+            }
+        });
+    }
+
+    clicked_tag
+}
+
+fn chart_placeholder(ui: &mut egui::Ui, width: f32, text: &str) {
+    ui.allocate_ui(egui::vec2(width, width / 1.6), |ui| {
+        ui.centered_and_justified(|ui| {
+            ui.label(text);
+        });
+    });
+}
+
+// Painted by hand, a Plot per entry would be too heavy for a long list
+fn sparkline(ui: &mut egui::Ui, points: &PlotPoints, span: i64, color: Color32) {
+    let (rect, _) = ui.allocate_exact_size(egui::vec2(60.0, ui.spacing().interact_size.y), Sense::hover());
+    let points = points.points();
+    if points.len() < 2 {
+        return;
+    }
+
+    let min_y = points.iter().map(|point| point.y).fold(f64::INFINITY, f64::min);
+    let max_y = points.iter().map(|point| point.y).fold(f64::NEG_INFINITY, f64::max);
+    let range_y = (max_y - min_y).max(0.1);
+
+    let line: Vec<egui::Pos2> = points.iter()
+        .map(|point| egui::pos2(
+            rect.left() + rect.width() * ((point.x + span as f64) / (2 * span) as f64) as f32,
+            rect.bottom() - 2.0 - (rect.height() - 4.0) * ((point.y - min_y) / range_y) as f32,
+        ))
+        .collect();
+
+    ui.painter().add(egui::Shape::line(line, egui::Stroke::new(1.0, color)));
+}
+
+fn highlight_matches(ui: &egui::Ui, text: &str, query: &str) -> LayoutJob {
+    let font_id = egui::TextStyle::Body.resolve(ui.style());
+    let normal = TextFormat::simple(font_id.clone(), ui.visuals().text_color());
+    let highlight = TextFormat {
+        background: Color32::YELLOW,
+        ..TextFormat::simple(font_id, Color32::BLACK)
+    };
+
+    let mut job = LayoutJob::default();
+
+    // Matching happens on the lowercase text, so only highlight when the byte offsets line up
+    let same_offsets = text.chars().all(|c| c.to_lowercase().map(char::len_utf8).sum::<usize>() == c.len_utf8());
+    if !same_offsets || query.is_empty() {
+        job.append(text, 0.0, normal);
+        return job;
+    }
+
+    let lower_text = text.to_lowercase();
+    let lower_query = query.to_lowercase();
+
+    let mut last = 0;
+    for (start, matched) in lower_text.match_indices(&lower_query) {
+        job.append(&text[last..start], 0.0, normal.clone());
+        job.append(&text[start..start + matched.len()], 0.0, highlight.clone());
+        last = start + matched.len();
+    }
+    job.append(&text[last..], 0.0, normal);
+
+    job
+}
+
+fn color_from(rgb: [u8; 3]) -> Color32 {
+    Color32::from_rgb(rgb[0], rgb[1], rgb[2])
+}
+
+fn replace_ignore_case(text: &str, find: &str, replace: &str) -> String {
+    let find = find.to_lowercase();
+    let mut result = String::with_capacity(text.len());
+    let mut index = 0;
+
+    // Compared char by char since lowercasing can change the byte length
+    while let Some(c) = text[index..].chars().next() {
+        let mut lower = String::new();
+        let mut end = index;
+        for next in text[index..].chars() {
+            if lower.len() >= find.len() {
+                break;
+            }
+            lower.extend(next.to_lowercase());
+            end += next.len_utf8();
+        }
+
+        if !find.is_empty() && lower == find {
+            result.push_str(replace);
+            index = end;
+        } else {
+            result.push(c);
+            index += c.len_utf8();
+        }
+    }
+
+    result
+}
+
+fn parse_measurement(field: Option<&str>) -> Result<f32, std::num::ParseFloatError> {
+    let field = field.unwrap_or("").trim();
+
+    if field.is_empty() {
+        Ok(0.0)
+    } else {
+        field.parse()
+    }
+}
+
+fn auto_y_bounds(points: &PlotPoints, fallback: (f64, f64)) -> (f64, f64) {
+    let values: Vec<f64> = points.points().iter()
+        .map(|point| point.y)
+        .filter(|y| y.is_finite())
+        .collect();
+
+    if values.len() < 2 {
+        return fallback;
+    }
+
+    let min = values.iter().cloned().fold(f64::INFINITY, f64::min);
+    let max = values.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+
+    // 5% margin on each side, with a floor so a flat series still has some height
+    let margin = ((max - min) * 0.05).max(0.5);
+
+    (min - margin, max + margin)
+}
+
+// Without enough points to scale to, the 20 below the maximum rounded up to a multiple of 5
+fn measurement_bounds(points: &PlotPoints, max_value: f32) -> (f64, f64) {
+    let max_value = ((max_value.floor() as i32 / 5 + 1) * 5) as f64;
+
+    auto_y_bounds(points, (max_value - 20.0, max_value))
+}
+
+// The date picker works in chrono dates, everything else in time ones
+fn to_naive_date(date: Date) -> chrono::NaiveDate {
+    chrono::NaiveDate::from_ymd_opt(date.year(), u8::from(date.month()) as u32, date.day() as u32).unwrap()
+}
+
+fn from_naive_date(date: chrono::NaiveDate) -> Option<Date> {
+    use chrono::Datelike;
+
+    let month = Month::try_from(date.month() as u8).ok()?;
+    Date::from_calendar_date(date.year(), month, date.day() as u8).ok()
+}
+
+fn shift_month(year: i32, month: Month, offset: i32) -> (i32, Month) {
+    let month_index = year * 12 + (month as i32 - 1) + offset;
+
+    (month_index.div_euclid(12), Month::try_from((month_index.rem_euclid(12) + 1) as u8).unwrap())
+}
+
+fn offset_to_date(curr_date: Date, offset: f64) -> Date {
+    // Grid marks can go past the representable dates when the axis is stretched far out
+    Date::from_julian_day(curr_date.to_julian_day().saturating_add(offset.round() as i32)).unwrap_or(curr_date)
+}
+
+// Drags and zooms stay off, only a plain click on the plot is picked up
+fn clicked_point(plot_ui: &egui_plot::PlotUi) -> Option<PlotPoint> {
+    if plot_ui.response().clicked() {
+        plot_ui.pointer_coordinate()
+    } else {
+        None
+    }
+}
+
+fn point_label(curr_date: Date, date_format: &OwnedFormatItem, name: &str, value: &PlotPoint, unit: &str) -> String {
+    let date_string = offset_to_date(curr_date, value.x).format(date_format).unwrap();
+
+    if name.is_empty() {
+        format!("{}\n{:.1}{}", date_string, value.y, unit)
+    } else {
+        format!("{}\n{}\n{:.1}{}", name, date_string, value.y, unit)
+    }
+}
+
+// Charts are plotted in kg and cm, the ticks are converted to the displayed units
+fn y_axis_scaled(factor: f64, grid_mark: GridMark) -> String {
+    if grid_mark.step_size * factor >= 1.0 {
+        format!("{:.0}", grid_mark.value * factor)
+    } else {
+        format!("{:.1}", grid_mark.value * factor)
+    }
+}
+
+fn x_axis_dates(curr_date: Date, axis_format: &OwnedFormatItem, grid_mark: GridMark, _: &RangeInclusive<f64>) -> String {
+    let grid_date = offset_to_date(curr_date, grid_mark.value);
+    let date_string = grid_date.format(axis_format).unwrap();
+
+    date_string
+}
+
+impl eframe::App for MyApp {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        // Check date
+        self.check_date();
+        self.check_weigh_in();
+        self.update_window_geometry(ctx);
+
+        if self.locked {
+            egui::CentralPanel::default().show(ctx, |ui| self.password_dialog_ui(ui));
+            return;
+        }
+
+        // Single key shortcuts are ignored while typing in a text box
+        let shortcuts_enabled = !ctx.wants_keyboard_input();
+
+        if shortcuts_enabled && !self.read_only && ctx.input_mut(|i| i.consume_key(egui::Modifiers::COMMAND, egui::Key::Z)) {
+            self.undo();
+        }
+
+        // Files dragged onto the window are imported straight away
+        let dropped_files = ctx.input(|i| i.raw.dropped_files.clone());
+        for file in dropped_files {
+            if let Some(path) = file.path {
+                let message = if self.read_only {
+                    String::from("The diary is read only")
+                } else {
+                    self.import_dropped_file(&path)
+                };
+                if !message.is_empty() {
+                    self.toast = Some((message, ctx.input(|i| i.time)));
+                }
+            }
+        }
+
+        let was_editing = matches!(self.mode, Mode::Edit);
+
+        // Distraction-free writing, Z only works while not typing
+        let toggle_focus = ctx.input_mut(|i| i.consume_key(egui::Modifiers::NONE, egui::Key::F11))
+            || (self.focus_mode && ctx.input_mut(|i| i.consume_key(egui::Modifiers::NONE, egui::Key::Escape)))
+            || (shortcuts_enabled && ctx.input_mut(|i| i.consume_key(egui::Modifiers::NONE, egui::Key::Z)));
+        if toggle_focus && matches!(self.mode, Mode::Main) && (self.focus_mode || !self.read_only) {
+            self.toggle_focus_mode();
+        }
+        if self.focus_mode {
+            egui::CentralPanel::default().show(ctx, |ui| self.focus_ui(ui));
+            return;
+        }
+
+        if shortcuts_enabled && ctx.input(|i| i.key_pressed(egui::Key::Questionmark) || i.key_pressed(egui::Key::F1)) {
+            self.show_help = !self.show_help;
+        }
+
+        if shortcuts_enabled && matches!(self.mode, Mode::Main) && ctx.input(|i| i.key_pressed(egui::Key::T)) {
+            self.go_to_today();
+        }
+
+        // Straight into writing today's entry
+        if shortcuts_enabled && !self.read_only && matches!(self.mode, Mode::Main) && ctx.input(|i| i.key_pressed(egui::Key::E)) {
+            self.search_query.clear();
+            self.tag_filter = None;
+            self.request_edit(self.today);
+        }
+
+        egui::SidePanel::right("ToDo").show(ctx, |ui| {
+            // Header
+            ui.horizontal(|ui| {
+                if ui.button("⚙").clicked() {
+                    self.show_settings = !self.show_settings;
+                }
+                if ui.button("Today").clicked() {
+                    self.go_to_today();
+                }
+
+                let lock = if self.read_only { "🔒" } else { "🔓" };
+                if ui.add_enabled(matches!(self.mode, Mode::Main), egui::Button::new(lock)).on_hover_text("Reading mode").clicked() {
+                    self.read_only = !self.read_only;
+                }
+
+                self.profile_selector_ui(ui);
+            });
+
+            egui::CollapsingHeader::new("Calendar")
+                .default_open(true)
+                .show(ui, |ui| self.calendar_ui(ui));
+
+            let num_overdue = self.overdue_sections().len();
+            if num_overdue > 0 {
+                ui.colored_label(Color32::RED, format!("⚠ {} overdue sections", num_overdue));
+            }
+
+            // ToDo section
+            egui::ScrollArea::vertical().show(ui, |ui| {
+                ui.vertical(|ui| {
+                    match self.mode {
+                        // In this mode you can click a Task or a Section to edit it,
+                        // the checkboxes to mark tasks as completed and add new Tasks
+                        // and Sections
+                        Mode::Main => {
+                            // Handle zooming
+                            if ui.input_mut(|i| i.consume_key(egui::Modifiers::COMMAND, egui::Key::Plus) || i.consume_key(egui::Modifiers::COMMAND, egui::Key::Equals)) {
+                                self.scale_factor += 0.2;
+
+                                if self.scale_factor > 3.0 {
+                                    self.scale_factor = 3.0;
+                                }
+
+                                ctx.set_pixels_per_point(self.scale_factor);
+                            }
+                            if ui.input_mut(|i| i.consume_key(egui::Modifiers::COMMAND, egui::Key::Minus)) {
+                                self.scale_factor -= 0.2;
+
+                                if self.scale_factor < 1.0 {
+                                    self.scale_factor = 1.0;
+                                }
+
+                                ctx.set_pixels_per_point(self.scale_factor);
+                            }
+
+                            // Handle graph zoom
+                            if shortcuts_enabled && ui.input(|i| i.key_pressed(egui::Key::ArrowLeft)) {
+                                self.zoom = Zoom::Day;
+                            }
+
+                            if shortcuts_enabled && ui.input(|i| i.key_pressed(egui::Key::ArrowRight)) {
+                                self.zoom = Zoom::Week;
+                            }
+
+                            if shortcuts_enabled && !self.read_only && ui.input(|i| i.key_pressed(egui::Key::C)) {
+                                let tasks = self.completed_task_count();
+                                let sections = self.emptied_section_count();
+
+                                if tasks > 0 || sections > 0 {
+                                    self.pending_delete = Some(PendingDelete::CompletedTasks { tasks, sections });
+                                }
+                            }
+
+                            if shortcuts_enabled && !self.read_only && ui.input(|i| i.key_pressed(egui::Key::S)) {
+                                self.sort_tasks_by_priority();
+                            }
+
+                            let progress: Vec<(usize, usize)> = (0..self.sections.len())
+                                .map(|idx| self.section_progress(idx))
+                                .collect();
+
+                            let curr_date = self.curr_date;
+                            let edit_sense = self.edit_sense();
+                            for (section, (done, total)) in self.sections.iter_mut().zip(progress) {
+                                ui.horizontal(|ui| {
+                                    let arrow = if section.collapsed { "▶" } else { "▼" };
+                                    if ui.add(Label::new(arrow).sense(Sense::click())).clicked() {
+                                        section.collapsed = !section.collapsed;
+                                    }
+
+                                    // Render Section title as clickable, if clicked edit it
+                                    let title = format!("{} ({}/{})", section.title, done, total);
+                                    if ui.add(Label::new(RichText::new(title).heading()).sense(edit_sense)).clicked() {
+                                        // Enter edit section mode
+                                        section.edit = true;
+                                        self.mode = Mode::Edit;
+                                    }
+
+                                    if let Some(due) = section.due {
+                                        let days = (due - curr_date).whole_days();
+                                        match days {
+                                            0 => ui.colored_label(Color32::ORANGE, "due today"),
+                                            1.. => ui.label(format!("{} days left", days)),
+                                            _ => ui.colored_label(Color32::RED, format!("{} days overdue", -days)),
+                                        };
+                                    }
+                                });
+
+                                if section.collapsed {
+                                    continue;
+                                }
+
+                                // Render Tasks as clickable, if clicked edit it
+                                for task in &mut section.tasks {
+                                    ui.horizontal(|ui| {
+                                        // Checking a parent by hand checks all of its steps
+                                        if ui.add_enabled(!self.read_only, egui::Checkbox::without_text(&mut task.done)).changed() {
+                                            for (_, done) in &mut task.subtasks {
+                                                *done = task.done;
+                                            }
+                                        }
+
+                                        let mut task_text = RichText::new(&task.text);
+                                        if let Some(color) = task.priority.color() {
+                                            task_text = task_text.color(color);
+                                        }
+
+                                        if ui.add(Label::new(task_text).sense(edit_sense)).clicked() {
+                                            task.edit = true;
+                                            self.mode = Mode::Edit;
+                                            self.first_time_edit = true;
+                                        }
+
+                                        if task.recurrence.is_some() {
+                                            ui.label("🔁");
+                                        }
+                                    });
+
+                                    if !task.note.is_empty() {
+                                        ui.indent("task_note", |ui| ui.label(RichText::new(&task.note).weak()));
+                                    }
+
+                                    if task.subtasks.len() > 0 {
+                                        ui.indent("subtasks", |ui| {
+                                            for (text, done) in &mut task.subtasks {
+                                                ui.add_enabled(!self.read_only, egui::Checkbox::new(done, text.as_str()));
+                                            }
+                                        });
+                                        task.done = task.subtasks.iter().all(|(_, done)| *done);
+                                    }
+                                }
+
+                                // Render an invisible Task used to add a Task
+                                let response = ui.add(Label::new("                             "));
+                                if response.clicked() && !self.read_only {
+                                    let empty = String::new();
+                                    section.add_task(&empty, true);
+                                    self.mode = Mode::Edit;
+                                    self.first_time_edit = true;
+                                }
+                            }
+                            ui.separator();
+
+                            // Render an invisible Section used to add a Section
+                            let response = ui.add(Label::new(RichText::new("                             ").heading()));
+                            if response.clicked() && !self.read_only {
+                                let empty = String::new();
+                                self.add_section(&empty, true);
+                                self.mode = Mode::Edit;
+                                self.first_time_edit = true;
+                            }
+
+                            if self.sections.len() > 0 && !self.read_only {
+                                self.copy_tasks_ui(ui);
+                            }
+
+                            if self.archived_tasks.len() > 0 {
+                                ui.collapsing("Archive", |ui| self.archive_ui(ui));
+                            }
+                        },
+
+                        Mode::Edit => {
+                            // Moves are applied after the loop, as sections are borrowed in it
+                            let mut task_move = None;
+                            let mut section_move = None;
+
+                            for (section_index, section) in self.sections.iter_mut().enumerate() {
+                                if section.edit {
+                                    ui.horizontal(|ui| {
+                                        let response = ui.add(TextEdit::singleline(&mut section.title));
+
+                                        if self.first_time_edit {
+                                            response.request_focus();
+                                            self.first_time_edit = false;
+                                        }
+
+                                        if ui.input_mut(|i| i.consume_key(egui::Modifiers::ALT, egui::Key::ArrowLeft)) {
+                                            if let Some(to) = section_index.checked_sub(1) {
+                                                section_move = Some((section_index, to));
+                                            }
+                                        }
+                                        if ui.input_mut(|i| i.consume_key(egui::Modifiers::ALT, egui::Key::ArrowRight)) {
+                                            section_move = Some((section_index, section_index + 1));
+                                        }
+
+                                        if ui.input(|i| i.key_pressed(egui::Key::Enter) || i.key_pressed(egui::Key::Escape)) {
+                                            self.mode = Mode::Main;
+                                            section.edit = false;
+                                        }
+
+                                        if ui.button("-").clicked() {
+                                            self.pending_delete = Some(PendingDelete::Section(section_index));
+                                        }
+                                    });
+
+                                    ui.horizontal(|ui| {
+                                        let mut has_due = section.due.is_some();
+                                        if ui.checkbox(&mut has_due, "Due").changed() {
+                                            section.due = if has_due { Some(self.curr_date.saturating_add(time::Duration::days(7))) } else { None };
+                                        }
+
+                                        if let Some(due) = &mut section.due {
+                                            let mut picked = to_naive_date(*due);
+                                            let id_salt = format!("due_{}", section_index);
+                                            if ui.add(egui_extras::DatePickerButton::new(&mut picked).id_salt(&id_salt)).changed() {
+                                                if let Some(picked) = from_naive_date(picked) {
+                                                    *due = picked;
+                                                }
+                                            }
+                                        }
+                                    });
+                                } else {
+                                    ui.heading(&section.title);
+                                }
+
+                                for (task_index, task) in section.tasks.iter_mut().enumerate() {
+                                    let note_id = egui::Id::new(("task_note", section_index, task_index));
+
+                                    ui.horizontal(|ui| {
+                                        ui.checkbox(&mut task.done, "");
+
+                                        if task.edit {
+                                            // Render edit text box for task
+                                            let response = ui.add(TextEdit::singleline(&mut task.text));
+
+                                            if self.first_time_edit {
+                                                response.request_focus();
+                                                self.first_time_edit = false;
+                                            }
+
+                                            // Alt + arrows move the task, or its whole section
+                                            if ui.input_mut(|i| i.consume_key(egui::Modifiers::ALT, egui::Key::ArrowUp)) {
+                                                if let Some(to) = task_index.checked_sub(1) {
+                                                    task_move = Some((section_index, task_index, to));
+                                                }
+                                            }
+                                            if ui.input_mut(|i| i.consume_key(egui::Modifiers::ALT, egui::Key::ArrowDown)) {
+                                                task_move = Some((section_index, task_index, task_index + 1));
+                                            }
+                                            if ui.input_mut(|i| i.consume_key(egui::Modifiers::ALT, egui::Key::ArrowLeft)) {
+                                                if let Some(to) = section_index.checked_sub(1) {
+                                                    section_move = Some((section_index, to));
+                                                }
+                                            }
+                                            if ui.input_mut(|i| i.consume_key(egui::Modifiers::ALT, egui::Key::ArrowRight)) {
+                                                section_move = Some((section_index, section_index + 1));
+                                            }
+
+                                            // Enter adds new lines in the note instead
+                                            let note_focused = ui.memory(|m| m.has_focus(note_id));
+                                            if ui.input(|i| (i.key_pressed(egui::Key::Enter) && !note_focused) || i.key_pressed(egui::Key::Escape)) {
+                                                self.mode = Mode::Main;
+                                                task.edit = false;
+                                            }
+
+                                            if ui.button(task.priority.label()).clicked() {
+                                                task.priority = task.priority.next();
+                                            }
+
+                                            egui::ComboBox::from_id_salt("recurrence")
+                                                .selected_text(Recurrence::label(task.recurrence))
+                                                .show_ui(ui, |ui| {
+                                                    ui.selectable_value(&mut task.recurrence, None, Recurrence::label(None));
+                                                    for recurrence in [Recurrence::Daily, Recurrence::Weekly, Recurrence::Weekdays] {
+                                                        ui.selectable_value(&mut task.recurrence, Some(recurrence), Recurrence::label(Some(recurrence)));
+                                                    }
+                                                });
+
+                                            if ui.button("-").clicked() {
+                                                self.pending_delete = Some(PendingDelete::Task(section_index, task_index));
+                                            }
+                                        } else {
+                                            // Render normally
+                                            ui.label(&task.text);
+                                        }
+                                    });
+
+                                    if task.edit {
+                                        ui.add(TextEdit::multiline(&mut task.note)
+                                            .id(note_id)
+                                            .desired_rows(2)
+                                            .hint_text("Note"));
+
+                                        // Steps of the task
+                                        ui.indent("subtasks", |ui| {
+                                            let mut removed = None;
+                                            for (step_index, (text, done)) in task.subtasks.iter_mut().enumerate() {
+                                                ui.horizontal(|ui| {
+                                                    ui.checkbox(done, "");
+                                                    ui.add(TextEdit::singleline(text).hint_text("Step"));
+                                                    if ui.button("-").clicked() {
+                                                        removed = Some(step_index);
+                                                    }
+                                                });
+                                            }
+                                            if let Some(step_index) = removed {
+                                                task.subtasks.remove(step_index);
+                                            }
+
+                                            if ui.button("+ Step").clicked() {
+                                                task.subtasks.push((String::new(), false));
+                                            }
+                                        });
+                                    }
+                                }
+
+                                ui.add_space(12.0);
+                            }
+
+                            // A pending delete holds indices, moving now would make it remove something else
+                            if self.pending_delete.is_none() {
+                                if let Some((section_index, from, to)) = task_move {
+                                    self.move_task(section_index, from, to);
+                                    self.first_time_edit = true;
+                                }
+                                if let Some((from, to)) = section_move {
+                                    self.move_section(from, to);
+                                    self.first_time_edit = true;
+                                }
+                            }
+
+                            ui.separator();
+                        },
+                    }
+                });
+            });
+        });
+
+        // Diary section
+        egui::CentralPanel::default().show(ctx, |ui| {
+            ui.vertical(|ui| {
+                // Quick weigh-in for today
+                ui.horizontal(|ui| {
+                    let weight_factor = self.units.weight_factor();
+                    let mut weight = self.quick_weight.unwrap_or(self.latest_weight().unwrap_or(70.0) * weight_factor);
+
+                    ui.label("⚖ Today");
+                    ui.add(DragValue::new(&mut weight).speed(0.1).range(0.0..=MAX_WEIGHT_KG * weight_factor).suffix(self.units.weight_suffix()));
+                    self.quick_weight = Some(weight);
+
+                    if ui.add_enabled(!self.read_only, egui::Button::new("Add")).clicked() {
+                        self.log_weight(weight / weight_factor);
+                    }
+                });
+
+                // Nudge to get back to writing
+                let has_today_entry = self.entries.iter().any(|entry| entry.date == self.today);
+                if let Some(days) = self.days_since_last_content() {
+                    if days > self.reminder_days && !has_today_entry && !self.reminder_dismissed {
+                        ui.horizontal(|ui| {
+                            ui.colored_label(Color32::ORANGE, format!("It's been {} days since your last entry.", days));
+                            if ui.small_button("✖").clicked() {
+                                self.reminder_dismissed = true;
+                            }
+                        });
+                    }
+                }
+
+                if self.weigh_in_due {
+                    ui.label(RichText::new("⚖ Time to weigh in").strong().color(Color32::BLACK).background_color(Color32::YELLOW));
+                }
+
+                // Several entries on one day would hide all but the first
+                let duplicates = self.duplicate_dates();
+                if duplicates.len() > 0 {
+                    let format = self.date_format_items.clone();
+                    let dates: Vec<String> = duplicates.iter().map(|date| date.format(&format).unwrap()).collect();
+
+                    ui.horizontal(|ui| {
+                        ui.colored_label(Color32::RED, format!("More than one entry on {}", dates.join(", ")));
+                        if ui.add_enabled(!self.read_only, egui::Button::new("Merge")).clicked() {
+                            self.merge_duplicates();
+                        }
+                    });
+                }
+
+                // Search bar
+                ui.horizontal(|ui| {
+                    ui.label("🔍");
+                    ui.add(TextEdit::singleline(&mut self.search_query).hint_text("Search entries"));
+
+                    if self.search_query.len() > 0 && ui.button("✖").clicked() {
+                        self.search_query.clear();
+                    }
+
+                    ui.toggle_value(&mut self.month_view, "📅 Month");
+
+                    if ui.toggle_value(&mut self.hide_locked, "🔏 Hide private").changed() {
+                        self.revealed.clear();
+                    }
+
+                    // Entry text size, the rest of the UI keeps its scale
+                    if ui.small_button("A-").clicked() {
+                        self.content_font_size = (self.content_font_size - 1.0).max(8.0);
+                    }
+                    if ui.small_button("A+").clicked() {
+                        self.content_font_size = (self.content_font_size + 1.0).min(40.0);
+                    }
+
+                    if ui.button("Review week").clicked() {
+                        self.show_review = !self.show_review;
+                    }
+
+                    if ui.add_enabled(!self.read_only, egui::Button::new("Replace…")).clicked() {
+                        self.show_replace = true;
+                        self.replace_message.clear();
+                        self.replace_count = None;
+                    }
+                });
+
+                // Writing stats
+                ui.horizontal(|ui| {
+                    ui.label(format!("🔥 {} day streak", self.current_streak()));
+                    ui.separator();
+                    ui.label(format!("{} words written", self.total_words()));
+
+                    if let Some(average) = self.rolling_average_weight(30) {
+                        ui.separator();
+                        ui.label(RichText::new(format!("30-day avg: {:.1}{}", average * self.units.weight_factor(), self.units.weight_suffix())).strong());
+                    }
+
+                    if let Some(whr) = self.latest_whr() {
+                        let color = if whr < 0.5 { Color32::GREEN } else { Color32::ORANGE };
+
+                        ui.separator();
+                        ui.label(RichText::new(format!("Waist/height: {:.2}", whr)).strong().color(color));
+                    }
+
+                    if let Some(change) = self.total_change() {
+                        let change = change * self.units.weight_factor();
+                        let (direction, color) = if change > 0.0 { ("Up", Color32::RED) } else { ("Down", Color32::GREEN) };
+
+                        ui.separator();
+                        ui.colored_label(color, format!("{} {:.1}{} since you started", direction, change.abs(), self.units.weight_suffix()));
+                    }
+                });
+
+                ui.collapsing("Statistics", |ui| self.stats_ui(ui));
+                ui.collapsing("Activity", |ui| self.heatmap_ui(ui));
+                ui.collapsing("Entries per month", |ui| self.entries_per_month_ui(ui));
+
+                // Graph controls
+                ui.horizontal(|ui| {
+                    ui.label("Trend");
+                    ui.add(DragValue::new(&mut self.trend_window).range(1..=60).suffix(" days"));
+
+                    ui.checkbox(&mut self.show_bmi, "BMI");
+                    ui.toggle_value(&mut self.overlay_charts, "Overlay weight and waist");
+                    ui.toggle_value(&mut self.weekly_average, "Weekly averages");
+
+                    if ui.add_enabled(self.weight_chart_rect.is_some(), egui::Button::new("Save chart")).clicked() {
+                        if let Some(path) = rfd::FileDialog::new()
+                            .add_filter("PNG", &["png"])
+                            .set_file_name("weight.png")
+                            .save_file() {
+                            let path = path.display().to_string();
+                            self.chart_message = match self.save_chart_png(ui.ctx(), &path) {
+                                Ok(()) => format!("Saved chart to {}", path),
+                                Err(error) => format!("Could not save chart: {}", error),
+                            };
+                        }
+                    }
+                    if !self.chart_message.is_empty() {
+                        ui.label(&self.chart_message);
+                    }
+
+                    egui::ComboBox::from_id_salt("extra_chart")
+                        .selected_text(self.extra_chart.label())
+                        .show_ui(ui, |ui| {
+                            for extra_chart in [ExtraChart::Sleep, ExtraChart::HeartRate, ExtraChart::Calories, ExtraChart::BodyFat, ExtraChart::WaistToHeight] {
+                                ui.selectable_value(&mut self.extra_chart, extra_chart, extra_chart.label());
+                            }
+                        });
+                });
+
+                if self.goal_reached() {
+                    ui.label(RichText::new("🎉 Goal weight reached!").heading().color(Color32::GREEN));
+                } else if let (Some(progress), Some(goal_weight_kg), Some(latest_weight)) = (self.goal_progress(), self.goal_weight_kg, self.latest_weight()) {
+                    let remaining = (latest_weight - goal_weight_kg).abs() * self.units.weight_factor();
+                    ui.add(egui::ProgressBar::new(progress)
+                        .desired_width(300.0)
+                        .text(format!("{:.1}{} to go", remaining, self.units.weight_suffix())));
+                }
+
+                // Section with graphs
+                let curr_date = self.curr_date;
+                let (min_x, max_x) = self.x_offset_range();
+                let axis_format = self.axis_date_format.clone();
+                let label_format = self.date_format_items.clone();
+                self.weight_chart_rect = None;
+                ui.horizontal(|ui| {
+                    // Plot formatters are move closures, they get a reference each
+                    let axis_format = &axis_format;
+                    let label_format = &label_format;
+                    let length_factor = self.units.length_factor() as f64;
+                    let length_suffix = self.units.length_suffix();
+                    let waist_axis_label = format!("Waist [{}]", self.length_unit_label());
+
+                    let waist_points = if self.weekly_charts() { self.get_waists_weekly() } else { self.get_waists() };
+                    let (min_waist_bound, max_waist_bound) = measurement_bounds(&waist_points, self.get_max_waist());
+
+                    let waist_line = Line::new("Waist", waist_points)
+                        .width(1.5)
+                        .color(color_from(self.waist_color));
+
+                    let chart_width = ui.available_width() / 3.0 - 20.0;
+                    let overlay = self.overlay_charts && !self.show_bmi;
+
+                    if overlay && self.get_max_weight() == 0.0 && self.get_max_waist() == 0.0 {
+                        chart_placeholder(ui, 2.0 * chart_width + ui.spacing().item_spacing.x, "No weight or waist data yet — add measurements to see your trend");
+                    } else if overlay {
+                        let chart = self.weight_chart_ui(ui, 2.0 * chart_width + ui.spacing().item_spacing.x);
+                        self.weight_chart_rect = Some(chart.response.rect);
+                        if let Some(point) = chart.inner {
+                            self.jump_to_offset(point.x);
+                        }
+                    } else if self.show_bmi && self.height_cm == 0.0 {
+                        chart_placeholder(ui, chart_width, "Set your height in the settings to see your BMI");
+                    } else if self.show_bmi {
+                        let bmi_points = self.get_bmi_points();
+                        let (min_bmi_bound, max_bmi_bound) = auto_y_bounds(&bmi_points, (15.0, 35.0));
+
+                        let bmi_line = Line::new("BMI", bmi_points)
+                            .width(1.5)
+                            .color(Color32::CYAN);
+
+                        Plot::new("bmi").view_aspect(1.6)
+                            .width(chart_width)
+                            .allow_boxed_zoom(false)
+                            .allow_double_click_reset(false)
+                            .allow_drag(false)
+                            .allow_scroll(false)
+                            .allow_zoom(false)
+                            .show_x(false)
+                            .include_x(min_x)
+                            .include_x(max_x)
+                            .default_y_bounds(min_bmi_bound.min(18.0), max_bmi_bound.max(25.5))
+                            .show_background(false)
+                            .x_axis_formatter(move |grid_mark, range| x_axis_dates(curr_date, axis_format, grid_mark, range))
+                            .label_formatter(move |name, value| point_label(curr_date, label_format, name, value, ""))
+                            .y_axis_label("BMI")
+                            .show(ui, |plot_ui| {
+                                // Healthy band
+                                plot_ui.hline(HLine::new("Healthy", 18.5)
+                                    .color(Color32::DARK_GREEN)
+                                    .style(LineStyle::dashed_loose()));
+                                plot_ui.hline(HLine::new("Healthy", 25.0)
+                                    .color(Color32::DARK_GREEN)
+                                    .style(LineStyle::dashed_loose()));
+
+                                plot_ui.line(bmi_line);
+                            });
+                    } else if self.get_max_weight() == 0.0 {
+                        chart_placeholder(ui, chart_width, "No weight data yet — add measurements to see your trend");
+                    } else {
+                        let chart = self.weight_chart_ui(ui, chart_width);
+                        self.weight_chart_rect = Some(chart.response.rect);
+                        if let Some(point) = chart.inner {
+                            self.jump_to_offset(point.x);
+                        }
+                    }
+                    if !overlay && self.get_max_waist() == 0.0 {
+                        chart_placeholder(ui, chart_width, "No waist data yet — add measurements to see your trend");
+                    } else if !overlay {
+                        Plot::new("waist").view_aspect(1.6)
+                            .width(chart_width)
+                            .allow_boxed_zoom(false)
+                            .allow_double_click_reset(false)
+                            .allow_drag(false)
+                            .allow_scroll(false)
+                            .allow_zoom(false)
+                            .show_x(false)
+                            .include_x(min_x)
+                            .include_x(max_x)
+                            .default_y_bounds(min_waist_bound, max_waist_bound)
+                            .show_background(false)
+                            .legend(Legend::default())
+                            .x_axis_formatter(move |grid_mark, range| x_axis_dates(curr_date, axis_format, grid_mark, range))
+                            .label_formatter(move |name, value| point_label(curr_date, label_format, name, &PlotPoint::new(value.x, value.y * length_factor), length_suffix))
+                            .y_axis_formatter(move |grid_mark, _| y_axis_scaled(length_factor, grid_mark))
+                            .y_axis_label(waist_axis_label.as_str())
+                            .show(ui, |plot_ui| plot_ui.line(waist_line));
+                    }
+
+                    let sleep_points = self.get_sleep_points();
+                    let (min_sleep_bound, max_sleep_bound) = auto_y_bounds(&sleep_points, (0.0, 12.0));
+
+                    let sleep_line = Line::new("Sleep", sleep_points)
+                        .width(1.5)
+                        .color(Color32::LIGHT_BLUE);
+
+                    if self.extra_chart == ExtraChart::WaistToHeight && self.height_cm == 0.0 {
+                        chart_placeholder(ui, chart_width, "Set your height in the settings to see your waist to height ratio");
+                    } else if self.extra_chart == ExtraChart::WaistToHeight && self.get_max_waist() == 0.0 {
+                        chart_placeholder(ui, chart_width, "No waist data yet — add measurements to see your waist to height ratio");
+                    } else if self.extra_chart == ExtraChart::WaistToHeight {
+                        let whr_points = self.get_whr_points();
+                        let (min_whr_bound, max_whr_bound) = auto_y_bounds(&whr_points, (0.4, 0.6));
+
+                        let whr_line = Line::new("Waist to height", whr_points)
+                            .width(1.5)
+                            .color(color_from(self.waist_color));
+
+                        Plot::new("waist_to_height").view_aspect(1.6)
+                            .width(chart_width)
+                            .allow_boxed_zoom(false)
+                            .allow_double_click_reset(false)
+                            .allow_drag(false)
+                            .allow_scroll(false)
+                            .allow_zoom(false)
+                            .show_x(false)
+                            .include_x(min_x)
+                            .include_x(max_x)
+                            .default_y_bounds(min_whr_bound.min(0.45), max_whr_bound.max(0.55))
+                            .show_background(false)
+                            .x_axis_formatter(move |grid_mark, range| x_axis_dates(curr_date, axis_format, grid_mark, range))
+                            .label_formatter(move |name, value| {
+                                let date_string = offset_to_date(curr_date, value.x).format(label_format).unwrap();
+                                format!("{}\n{}\n{:.2}", name, date_string, value.y)
+                            })
+                            .y_axis_label("Waist / height")
+                            .show(ui, |plot_ui| {
+                                // Below 0.5 is the healthy range
+                                plot_ui.hline(HLine::new("Healthy", 0.5)
+                                    .color(Color32::DARK_GREEN)
+                                    .style(LineStyle::dashed_loose()));
+
+                                plot_ui.line(whr_line);
+                            });
+                    } else if self.extra_chart == ExtraChart::Calories && self.entries.iter().all(|entry| entry.calories().is_none()) {
+                        chart_placeholder(ui, chart_width, "No calorie data yet — log what you eat to see it here");
+                    } else if self.extra_chart == ExtraChart::Calories {
+                        let calorie_points = self.get_calorie_points();
+                        let (mut min_calorie_bound, mut max_calorie_bound) = auto_y_bounds(&calorie_points, (1000.0, 3000.0));
+
+                        if let Some(calorie_goal) = self.calorie_goal {
+                            min_calorie_bound = min_calorie_bound.min(calorie_goal as f64 - 100.0);
+                            max_calorie_bound = max_calorie_bound.max(calorie_goal as f64 + 100.0);
+                        }
+
+                        let calorie_line = Line::new("Calories", calorie_points)
+                            .width(1.5)
+                            .color(Color32::GOLD);
+
+                        Plot::new("calories").view_aspect(1.6)
+                            .width(chart_width)
+                            .allow_boxed_zoom(false)
+                            .allow_double_click_reset(false)
+                            .allow_drag(false)
+                            .allow_scroll(false)
+                            .allow_zoom(false)
+                            .show_x(false)
+                            .include_x(min_x)
+                            .include_x(max_x)
+                            .default_y_bounds(min_calorie_bound, max_calorie_bound)
+                            .show_background(false)
+                            .x_axis_formatter(move |grid_mark, range| x_axis_dates(curr_date, axis_format, grid_mark, range))
+                            .label_formatter(move |name, value| point_label(curr_date, label_format, name, value, " kcal"))
+                            .y_axis_label("Calories [kcal]")
+                            .show(ui, |plot_ui| {
+                                plot_ui.line(calorie_line);
+
+                                if let Some(calorie_goal) = self.calorie_goal {
+                                    plot_ui.hline(HLine::new("Target", calorie_goal as f64)
+                                        .color(Color32::GREEN)
+                                        .style(LineStyle::dashed_loose()));
+                                }
+                            });
+                    } else if self.extra_chart == ExtraChart::BodyFat && self.entries.iter().all(|entry| entry.body_fat_pct().is_none()) {
+                        chart_placeholder(ui, chart_width, "No body fat data yet — log your body fat % to see it here");
+                    } else if self.extra_chart == ExtraChart::BodyFat {
+                        let bodyfat_line = Line::new("Body fat", self.get_bodyfat_points())
+                            .width(1.5)
+                            .color(Color32::LIGHT_GREEN);
+
+                        Plot::new("body_fat").view_aspect(1.6)
+                            .width(chart_width)
+                            .allow_boxed_zoom(false)
+                            .allow_double_click_reset(false)
+                            .allow_drag(false)
+                            .allow_scroll(false)
+                            .allow_zoom(false)
+                            .show_x(false)
+                            .include_x(min_x)
+                            .include_x(max_x)
+                            .default_y_bounds(0.0, 50.0)
+                            .show_background(false)
+                            .x_axis_formatter(move |grid_mark, range| x_axis_dates(curr_date, axis_format, grid_mark, range))
+                            .label_formatter(move |name, value| point_label(curr_date, label_format, name, value, " %"))
+                            .y_axis_label("Body fat [%]")
+                            .show(ui, |plot_ui| plot_ui.line(bodyfat_line));
+                    } else if self.extra_chart == ExtraChart::HeartRate && self.entries.iter().all(|entry| entry.resting_hr().is_none()) {
+                        chart_placeholder(ui, chart_width, "No heart rate data yet — log your resting HR to see it here");
+                    } else if self.extra_chart == ExtraChart::HeartRate {
+                        let hr_points = self.get_hr_points();
+                        let (min_hr_bound, max_hr_bound) = auto_y_bounds(&hr_points, (40.0, 100.0));
+
+                        let hr_line = Line::new("Resting HR", hr_points)
+                            .width(1.5)
+                            .color(Color32::LIGHT_RED);
+
+                        Plot::new("resting_hr").view_aspect(1.6)
+                            .width(chart_width)
+                            .allow_boxed_zoom(false)
+                            .allow_double_click_reset(false)
+                            .allow_drag(false)
+                            .allow_scroll(false)
+                            .allow_zoom(false)
+                            .show_x(false)
+                            .include_x(min_x)
+                            .include_x(max_x)
+                            .default_y_bounds(min_hr_bound, max_hr_bound)
+                            .show_background(false)
+                            .x_axis_formatter(move |grid_mark, range| x_axis_dates(curr_date, axis_format, grid_mark, range))
+                            .label_formatter(move |name, value| point_label(curr_date, label_format, name, value, " bpm"))
+                            .y_axis_label("Resting HR [bpm]")
+                            .show(ui, |plot_ui| plot_ui.line(hr_line));
+                    } else if self.entries.iter().all(|entry| entry.sleep_hours() == 0.0) {
+                        chart_placeholder(ui, chart_width, "No sleep data yet — log your sleep to see it here");
+                    } else {
+                        Plot::new("sleep").view_aspect(1.6)
+                            .width(chart_width)
+                            .allow_boxed_zoom(false)
+                            .allow_double_click_reset(false)
+                            .allow_drag(false)
+                            .allow_scroll(false)
+                            .allow_zoom(false)
+                            .show_x(false)
+                            .include_x(min_x)
+                            .include_x(max_x)
+                            .default_y_bounds(min_sleep_bound, max_sleep_bound)
+                            .show_background(false)
+                            .x_axis_formatter(move |grid_mark, range| x_axis_dates(curr_date, axis_format, grid_mark, range))
+                            .label_formatter(move |name, value| point_label(curr_date, label_format, name, value, " h"))
+                            .y_axis_label("Sleep [h]")
+                            .show(ui, |plot_ui| plot_ui.line(sleep_line));
+                    }
+                });
+
+                // One chart per custom metric
+                if self.metrics.len() > 0 {
+                    let axis_format = &axis_format;
+                    let label_format = &label_format;
+                    let chart_width = ui.available_width() / 3.0 - 20.0;
+
+                    ui.horizontal_wrapped(|ui| {
+                        for metric in &self.metrics {
+                            if self.entries.iter().all(|entry| !entry.values.contains_key(&metric.name)) {
+                                chart_placeholder(ui, chart_width, &format!("No {} data yet", metric.name));
+                                continue;
+                            }
+
+                            let points = self.get_metric_points(&metric.name);
+                            let (min_bound, max_bound) = auto_y_bounds(&points, (0.0, 10.0));
+                            let line = Line::new(metric.name.as_str(), points)
+                                .width(1.5)
+                                .color(color_from(metric.color));
+
+                            let unit = if metric.unit.is_empty() { String::new() } else { format!(" {}", metric.unit) };
+                            let axis_label = if metric.unit.is_empty() { metric.name.clone() } else { format!("{} [{}]", metric.name, metric.unit) };
+
+                            Plot::new(("metric", metric.name.as_str())).view_aspect(1.6)
+                                .width(chart_width)
+                                .allow_boxed_zoom(false)
+                                .allow_double_click_reset(false)
+                                .allow_drag(false)
+                                .allow_scroll(false)
+                                .allow_zoom(false)
+                                .show_x(false)
+                                .include_x(min_x)
+                                .include_x(max_x)
+                                .default_y_bounds(min_bound, max_bound)
+                                .show_background(false)
+                                .x_axis_formatter(move |grid_mark, range| x_axis_dates(curr_date, axis_format, grid_mark, range))
+                                .label_formatter(move |name, value| point_label(curr_date, label_format, name, value, &unit))
+                                .y_axis_label(axis_label)
+                                .show(ui, |plot_ui| plot_ui.line(line));
+                        }
+                    });
+                }
+
+                if let Some(goal_date) = self.project_goal_date() {
+                    let format = self.date_format_items.clone();
+                    let date_string = goal_date.format(&format).unwrap();
+                    ui.label(format!("At this rate you will reach your goal weight on {}", date_string));
+                }
+
+                // Section with diary entries
+                egui::ScrollArea::vertical().show(ui, |ui| {
+                    // While searching only the matching entries are shown
+                    if self.search_query.len() > 0 {
+                        self.search_results_ui(ui);
+                        return;
+                    }
+
+                    // Same when filtering by a tag
+                    if let Some(tag) = self.tag_filter.clone() {
+                        self.tag_results_ui(ui, &tag);
+                        return;
+                    }
+
+                    // Month overview replaces the list until a day is opened
+                    if self.month_view && matches!(self.mode, Mode::Main) {
+                        self.month_grid_ui(ui);
+                        return;
+                    }
+
+                    if matches!(self.mode, Mode::Main) && self.on_this_day(self.curr_date).len() > 0 {
+                        self.on_this_day_ui(ui);
+                    }
+
+                    // If there is no entry for the selected day, add a prompt for it
+                    if self.get_entry_by_date(self.curr_date).is_none() && !self.read_only {
+                        let format = self.date_format_items.clone();
+                        let date_string = self.curr_date.format(&format).unwrap();
+                        ui.heading(date_string);
+
+                        let prompt = if self.curr_date == self.today { "Add entry for today!" } else { "Add entry for this day!" };
+                        if ui.add(Label::new(prompt).sense(Sense::click())).clicked() {
+                            self.edit_entry(self.curr_date);
                         }
+
+                        ui.add_space(10.0);
+                    }
+
+                    // Rest of entries
+                    match self.mode {
+                        Mode::Main => {
+                            // Toggle redux mode
+                            if shortcuts_enabled && ui.input(|i| i.key_pressed(egui::Key::R)) {
+                                self.redux_mode = !self.redux_mode;
+                            }
+
+                            // Jump between written entries, newer ones are further up the list
+                            let mut jump_date = None;
+                            if shortcuts_enabled && ui.input(|i| i.key_pressed(egui::Key::N) || i.key_pressed(egui::Key::PageUp)) {
+                                jump_date = self.next_content_entry(self.curr_date);
+                            }
+                            if shortcuts_enabled && ui.input(|i| i.key_pressed(egui::Key::P) || i.key_pressed(egui::Key::PageDown)) {
+                                jump_date = self.prev_content_entry(self.curr_date);
+                            }
+                            if let Some(date) = jump_date {
+                                self.curr_date = date;
+                                self.scroll_to_date = Some(date);
+                            }
+
+                            // Arrows move the selection over the entries on screen, Enter opens it
+                            let visible: Vec<Date> = self.entries.iter()
+                                .filter(|entry| !self.redux_mode || entry.content.len() > 0)
+                                .map(|entry| entry.date)
+                                .collect();
+                            let position = self.selected_entry.and_then(|selected| visible.iter().position(|&date| date == selected));
+                            let mut moved = None;
+                            if shortcuts_enabled && visible.len() > 0 && ui.input(|i| i.key_pressed(egui::Key::ArrowDown)) {
+                                moved = Some(visible[position.map_or(0, |position| (position + 1).min(visible.len() - 1))]);
+                            }
+                            if shortcuts_enabled && visible.len() > 0 && ui.input(|i| i.key_pressed(egui::Key::ArrowUp)) {
+                                moved = Some(visible[position.map_or(0, |position| position.saturating_sub(1))]);
+                            }
+                            if let Some(date) = moved {
+                                self.selected_entry = Some(date);
+                                self.scroll_to_date = Some(date);
+                            }
+                            if shortcuts_enabled && !self.read_only && ui.input(|i| i.key_pressed(egui::Key::Enter)) {
+                                if let Some(date) = self.selected_entry.filter(|date| visible.contains(date)) {
+                                    self.request_edit(date);
+                                }
+                            }
+
+                            // Private entries ask before opening in the editor
+                            let mut private_edit = None;
+
+                            let mut clicked_tag = None;
+
+                            if self.entries.iter().any(|entry| entry.pinned) {
+                                self.pinned_ui(ui);
+                            }
+
+                            // One sorted pass, each row then only searches it
+                            let readings = self.weight_readings();
+                            let weight_deltas: Vec<Option<f32>> = self.entries.iter()
+                                .map(|entry| MyApp::weight_delta(&readings, entry.date))
+                                .collect();
+
+                            let sparklines: Vec<PlotPoints> = self.entries.iter()
+                                .map(|entry| MyApp::weights_around(&readings, entry.date, 3))
+                                .collect();
+
+                            let edit_sense = self.edit_sense();
+                            let format = self.date_format_items.clone();
+                            for ((entry, weight_delta), sparkline_points) in self.entries.iter_mut().zip(weight_deltas).zip(sparklines) {
+                                let date_string = entry.date.format(&format).unwrap();
+
+                                if self.hide_locked && entry.locked {
+                                    continue;
+                                }
+
+                                if !self.redux_mode || entry.content.len() > 0 {
+                                    // Filled in once the header size is known
+                                    let highlight = ui.painter().add(egui::Shape::Noop);
+
+                                    let header = ui.horizontal(|ui| {
+                                        let mut weight_string = String::from("--");
+
+                                        if entry.weight_kg() != 0.0 {
+                                            weight_string = format!("{:.1}", entry.weight_kg() * self.units.weight_factor());
+                                        }
+                                        weight_string.push_str(self.units.weight_suffix());
+
+                                        let mut waist_string = String::from("--");
+                                        if entry.waist_cm() != 0.0 {
+                                            waist_string = format!("{:.1}", entry.waist_cm() * self.units.length_factor());
+                                        }
+                                        waist_string.push_str(self.units.length_suffix());
+
+                                        let mut mood_string = String::from("--");
+                                        if let Some(mood) = entry.mood {
+                                            mood_string = format!("{}", mood);
+                                        }
+                                        mood_string.push_str(" ★");
+
+                                        let mut sleep_string = String::from("--");
+                                        if entry.sleep_hours() != 0.0 {
+                                            sleep_string = format!("{:.1}", entry.sleep_hours());
+                                        }
+                                        sleep_string.push_str(" h");
+
+                                        let mut hr_string = String::from("--");
+                                        if let Some(resting_hr) = entry.resting_hr() {
+                                            hr_string = format!("{}", resting_hr);
+                                        }
+                                        hr_string.push_str(" bpm");
+
+                                        let mut calorie_string = String::from("--");
+                                        if let Some(calories) = entry.calories() {
+                                            calorie_string = format!("{}", calories);
+                                        }
+                                        calorie_string.push_str(" kcal");
+
+                                        if ui.add(Label::new(RichText::new(date_string).heading()).sense(edit_sense)).clicked() {
+                                            if entry.locked {
+                                                private_edit = Some(entry.date);
+                                            } else {
+                                                entry.edit = true;
+                                                self.mode = Mode::Edit;
+                                                self.first_time_edit = true;
+                                            }
+                                        }
+                                        if entry.date == self.today {
+                                            let selection = ui.visuals().selection;
+                                            ui.label(RichText::new(" Today ").small().strong()
+                                                .color(selection.stroke.color)
+                                                .background_color(selection.bg_fill));
+                                        }
+                                        ui.label(weight_string);
+                                        if let Some(weight_delta) = weight_delta {
+                                            let delta = weight_delta * self.units.weight_factor();
+                                            let color = if delta > 0.0 { Color32::RED } else { Color32::GREEN };
+                                            ui.colored_label(color, format!("({:+.1})", delta));
+                                        }
+                                        sparkline(ui, &sparkline_points, 3, color_from(self.weight_color));
+                                        ui.label(waist_string);
+                                        ui.label(mood_string);
+                                        ui.label(sleep_string);
+                                        ui.label(hr_string);
+                                        ui.label(calorie_string);
+                                        if let Some(body_fat_pct) = entry.body_fat_pct() {
+                                            ui.label(format!("{:.1} % fat", body_fat_pct));
+                                        }
+                                        for metric in &self.metrics {
+                                            if let Some(value) = entry.values.get(&metric.name) {
+                                                ui.label(format!("{} {:.1} {}", metric.name, value, metric.unit));
+                                            }
+                                        }
+                                        if entry.workouts.len() > 0 {
+                                            ui.label(format!("🏋 {}", entry.workouts.len()));
+                                        }
+
+                                        if ui.small_button("Copy").clicked() {
+                                            ui.ctx().copy_text(entry.to_plain_text(self.units, &format, &self.revealed, &self.metrics));
+                                        }
+                                        if ui.add_enabled(!self.read_only, egui::SelectableLabel::new(entry.pinned, "📌")).clicked() {
+                                            entry.pinned = !entry.pinned;
+                                        }
+                                        if ui.add_enabled(!self.read_only, egui::SelectableLabel::new(entry.locked, "🔏")).on_hover_text("Private").clicked() {
+                                            entry.locked = !entry.locked;
+                                        }
+                                    });
+
+                                    // Accent colours come from the theme so they work in light and dark mode
+                                    let accent = ui.visuals().selection.bg_fill;
+                                    if self.selected_entry == Some(entry.date) {
+                                        ui.painter().set(highlight, egui::Shape::rect_filled(header.response.rect.expand(2.0), 4.0, accent.linear_multiply(0.3)));
+                                    } else if entry.date == self.today {
+                                        ui.painter().set(highlight, egui::Shape::rect_filled(header.response.rect.expand(2.0), 4.0, accent.linear_multiply(0.15)));
+                                    }
+
+                                    if self.scroll_to_date == Some(entry.date) {
+                                        header.response.scroll_to_me(Some(egui::Align::TOP));
+                                        self.scroll_to_date = None;
+                                    }
+                                }
+
+                                if let Some(tag) = tag_chips(ui, &entry.tags, None) {
+                                    clicked_tag = Some(tag);
+                                }
+
+                                // Private entries stay hidden until clicked, the header is still shown
+                                if entry.is_hidden(&self.revealed) {
+                                    if ui.add(Label::new(RichText::new("[locked]").weak()).sense(Sense::click())).on_hover_text("Click to reveal").clicked() {
+                                        self.revealed.insert(entry.date);
+                                    }
+                                    ui.add_space(10.0);
+                                    continue;
+                                }
+
+                                // Rendered Markdown isn't clickable so its links still work, the date heading opens the editor
+                                if entry.content.len() > 0 && self.render_markdown {
+                                    ui.scope(|ui| {
+                                        if let Some(font_id) = ui.style_mut().text_styles.get_mut(&egui::TextStyle::Body) {
+                                            font_id.size = self.content_font_size;
+                                        }
+                                        CommonMarkViewer::new().show(ui, &mut self.markdown_cache, &entry.content);
+                                    });
+                                    ui.add_space(10.0);
+                                } else if entry.content.len() > 0 {
+                                    if ui.add(Label::new(RichText::new(&entry.content).size(self.content_font_size)).sense(edit_sense)).clicked() {
+                                        if entry.locked {
+                                            private_edit = Some(entry.date);
+                                        } else {
+                                            entry.edit = true;
+                                            self.mode = Mode::Edit;
+                                            self.first_time_edit = true;
+                                        }
+                                    }
+                                    ui.add_space(10.0);
+                                }
+
+                                if entry.workouts.len() > 0 {
+                                    for workout in &entry.workouts {
+                                        ui.label(format!("• {}", workout));
+                                    }
+                                    ui.add_space(10.0);
+                                }
+
+                                if entry.image_paths.len() > 0 {
+                                    ui.horizontal(|ui| {
+                                        for path in &entry.image_paths {
+                                            let thumbnail = egui::Image::new(format!("file://{}", path))
+                                                .max_height(80.0)
+                                                .sense(Sense::click());
+                                            if ui.add(thumbnail).on_hover_text(path).clicked() {
+                                                self.viewing_image = Some(path.clone());
+                                            }
+                                        }
+                                    });
+                                    ui.add_space(10.0);
+                                }
+                            }
+
+                            if let Some(tag) = clicked_tag {
+                                self.toggle_tag_filter(tag);
+                            }
+                            if private_edit.is_some() {
+                                self.pending_private_edit = private_edit;
+                            }
+                        },
+
+                        Mode::Edit => {
+                            let format = self.date_format_items.clone();
+                            let all_tags = self.all_tags();
+                            let taken_dates: HashSet<Date> = self.entries.iter().map(|entry| entry.date).collect();
+                            let mut moved_date = None;
+                            let mut closed_date = None;
+                            for entry in &mut self.entries {
+                                let date_string = entry.date.format(&format).unwrap();
+
+                                if self.hide_locked && entry.locked && !entry.edit {
+                                    continue;
+                                }
+
+                                if entry.edit {
+                                    let header = ui.horizontal(|ui| {
+                                        ui.heading(date_string);
+
+                                        // Backdating, another entry on the new day is never overwritten
+                                        ui.menu_button("📅", |ui| {
+                                            let (mut year, mut month, mut day) = (entry.date.year(), entry.date.month() as u8, entry.date.day());
+                                            let mut changed = false;
+                                            ui.horizontal(|ui| {
+                                                changed |= ui.add(DragValue::new(&mut day).range(1..=31)).changed();
+                                                changed |= ui.add(DragValue::new(&mut month).range(1..=12)).changed();
+                                                changed |= ui.add(DragValue::new(&mut year).range(1900..=9999)).changed();
+                                            });
+
+                                            let new_date = Month::try_from(month).ok()
+                                                .and_then(|month| Date::from_calendar_date(year, month, day.min(time::util::days_in_year_month(year, month))).ok());
+                                            if let Some(new_date) = new_date.filter(|new_date| changed && *new_date != entry.date) {
+                                                if taken_dates.contains(&new_date) {
+                                                    self.date_edit_error = Some(format!("There is already an entry on {}", new_date.format(&format).unwrap()));
+                                                } else {
+                                                    entry.date = new_date;
+                                                    moved_date = Some(new_date);
+                                                    self.date_edit_error = None;
+                                                }
+                                            }
+
+                                            if let Some(error) = &self.date_edit_error {
+                                                ui.colored_label(Color32::RED, error);
+                                            }
+                                        });
+
+                                        // Edit in the displayed units, store in metric
+                                        let weight_factor = self.units.weight_factor();
+                                        let mut weight = entry.weight_kg() * weight_factor;
+                                        let weight_response = ui.add(DragValue::new(&mut weight).speed(0.1).range(0.0..=MAX_WEIGHT_KG * weight_factor));
+                                        if weight_response.changed() {
+                                            entry.set_value(WEIGHT, (weight / weight_factor).clamp(0.0, MAX_WEIGHT_KG));
+                                        }
+                                        ui.label(self.units.weight_suffix());
+
+                                        let length_factor = self.units.length_factor();
+                                        let mut waist = entry.waist_cm() * length_factor;
+                                        let waist_response = ui.add(DragValue::new(&mut waist).speed(0.1).range(0.0..=MAX_WAIST_CM * length_factor));
+                                        if waist_response.changed() {
+                                            entry.set_value(WAIST, (waist / length_factor).clamp(0.0, MAX_WAIST_CM));
+                                        }
+                                        ui.label(self.units.length_suffix());
+
+                                        // Clicking the selected mood again clears it
+                                        for mood in 1..=5 {
+                                            if ui.selectable_label(entry.mood == Some(mood), format!("{}", mood)).clicked() {
+                                                if entry.mood == Some(mood) {
+                                                    entry.mood = None;
+                                                } else {
+                                                    entry.mood = Some(mood);
+                                                }
+                                            }
+                                        }
+                                        ui.label(" ★");
+
+                                        // Zero means no reading
+                                        let mut sleep_hours = entry.sleep_hours();
+                                        if ui.add(DragValue::new(&mut sleep_hours).speed(0.1).range(0.0..=24.0)).changed() {
+                                            entry.set_value(SLEEP, sleep_hours);
+                                        }
+                                        ui.label(" h");
+
+                                        let mut resting_hr = entry.resting_hr().unwrap_or(0);
+                                        if ui.add(DragValue::new(&mut resting_hr).range(0..=250)).changed() {
+                                            entry.set_value(RESTING_HR, resting_hr as f32);
+                                        }
+                                        ui.label(" bpm");
+
+                                        let mut calories = entry.calories().unwrap_or(0);
+                                        if ui.add(DragValue::new(&mut calories).speed(10).range(0..=20000)).changed() {
+                                            entry.set_value(CALORIES, calories as f32);
+                                        }
+                                        ui.label(" kcal");
+
+                                        let mut body_fat_pct = entry.body_fat_pct().unwrap_or(0.0);
+                                        if ui.add(DragValue::new(&mut body_fat_pct).speed(0.1).range(0.0..=60.0)).changed() {
+                                            entry.set_value(BODY_FAT, body_fat_pct);
+                                        }
+                                        ui.label(" % fat");
+
+                                        for metric in &self.metrics {
+                                            let mut value = entry.value(&metric.name).unwrap_or(0.0);
+                                            if ui.add(DragValue::new(&mut value).speed(0.1).prefix(format!("{} ", metric.name)).suffix(format!(" {}", metric.unit))).changed() {
+                                                entry.set_value(&metric.name, value);
+                                            }
+                                        }
+
+                                        if ui.button("-").clicked() {
+                                            self.pending_delete = Some(PendingDelete::Entry(entry.date));
+                                        }
+
+                                        (weight_response.id, waist_response.id)
+                                    });
+
+                                    let response = ui.add_sized([ui.available_width(), 1.0], TextEdit::multiline(&mut entry.content));
+
+                                    // Tab cycles weight -> waist -> content and around, Shift+Tab goes back
+                                    let (weight_id, waist_id) = header.inner;
+                                    let fields = [weight_id, waist_id, response.id];
+                                    if let Some(index) = fields.iter().position(|id| ui.memory(|m| m.has_focus(*id))) {
+                                        let mut next = None;
+                                        if ui.input_mut(|i| i.consume_key(egui::Modifiers::SHIFT, egui::Key::Tab)) {
+                                            next = Some(fields[(index + fields.len() - 1) % fields.len()]);
+                                        } else if ui.input_mut(|i| i.consume_key(egui::Modifiers::NONE, egui::Key::Tab)) {
+                                            next = Some(fields[(index + 1) % fields.len()]);
+                                        }
+
+                                        if let Some(next) = next {
+                                            ui.memory_mut(|m| {
+                                                m.request_focus(next);
+                                                m.move_focus(egui::FocusDirection::None);
+                                            });
+                                        }
+                                    }
+
+                                    // Ctrl+T inserts the current time at the caret
+                                    if response.has_focus() && ui.input_mut(|i| i.consume_key(egui::Modifiers::COMMAND, egui::Key::T)) {
+                                        if let Some(mut state) = TextEdit::load_state(ui.ctx(), response.id) {
+                                            let format = format_description::parse("[hour]:[minute]").unwrap();
+                                            let timestamp = format!("[{}] ", OffsetDateTime::now_local().unwrap_or_else(|_| OffsetDateTime::now_utc()).format(&format).unwrap());
+
+                                            // The cursor counts chars, not bytes
+                                            let caret = state.cursor.char_range()
+                                                .map_or(entry.content.chars().count(), |range| range.primary.index);
+                                            let byte_index = entry.content.char_indices()
+                                                .nth(caret)
+                                                .map_or(entry.content.len(), |(index, _)| index);
+                                            entry.content.insert_str(byte_index, &timestamp);
+
+                                            let caret = egui::text::CCursor::new(caret + timestamp.chars().count());
+                                            state.cursor.set_char_range(Some(egui::text::CCursorRange::one(caret)));
+                                            state.store(ui.ctx(), response.id);
+                                        }
+                                    }
+
+                                    if self.first_time_edit {
+                                        response.request_focus();
+                                        self.first_time_edit = false;
+                                    }
+
+                                    // The text is only parsed back while typing so commas aren't eaten
+                                    let tags_id = egui::Id::new(("tags", entry.date));
+                                    let tags_focused = ui.memory(|m| m.has_focus(tags_id));
+                                    if !tags_focused {
+                                        entry.tags_text = entry.tags.join(", ");
+                                    }
+
+                                    // Suggestions complete the tag being typed, the one after the last comma
+                                    let typed = entry.tags_text.rsplit(',').next().unwrap_or("").trim().to_lowercase();
+                                    let suggestions: Vec<&String> = all_tags.iter()
+                                        .filter(|tag| typed.len() > 0 && tag.to_lowercase().starts_with(&typed) && tag.to_lowercase() != typed)
+                                        .filter(|tag| !entry.tags.contains(tag))
+                                        .take(5)
+                                        .collect();
+                                    self.tag_suggestion = self.tag_suggestion.min(suggestions.len().saturating_sub(1));
+
+                                    // Keys are taken before the text box sees them, Enter would otherwise drop focus
+                                    let mut accepted = None;
+                                    if tags_focused && suggestions.len() > 0 {
+                                        if ui.input_mut(|i| i.consume_key(egui::Modifiers::NONE, egui::Key::ArrowDown)) {
+                                            self.tag_suggestion = (self.tag_suggestion + 1) % suggestions.len();
+                                        }
+                                        if ui.input_mut(|i| i.consume_key(egui::Modifiers::NONE, egui::Key::ArrowUp)) {
+                                            self.tag_suggestion = (self.tag_suggestion + suggestions.len() - 1) % suggestions.len();
+                                        }
+                                        if ui.input_mut(|i| i.consume_key(egui::Modifiers::NONE, egui::Key::Enter)) {
+                                            accepted = Some(suggestions[self.tag_suggestion].clone());
+                                        }
+                                    }
+
+                                    let response = ui.add(TextEdit::singleline(&mut entry.tags_text)
+                                        .id(tags_id)
+                                        .hint_text("Tags, separated by commas"));
+                                    if response.changed() {
+                                        entry.tags = parse_tags(&entry.tags_text);
+                                        self.tag_suggestion = 0;
+                                    }
+
+                                    if tags_focused && suggestions.len() > 0 {
+                                        ui.indent("tag_suggestions", |ui| {
+                                            for (index, suggestion) in suggestions.iter().enumerate() {
+                                                if ui.selectable_label(index == self.tag_suggestion, format!("#{}", suggestion)).clicked() {
+                                                    accepted = Some(suggestion.to_string());
+                                                }
+                                            }
+                                        });
+                                    }
+
+                                    if let Some(tag) = accepted {
+                                        let head = match entry.tags_text.rfind(',') {
+                                            Some(index) => format!("{} ", &entry.tags_text[..=index]),
+                                            None => String::new(),
+                                        };
+                                        entry.tags_text = format!("{}{}, ", head, tag);
+                                        entry.tags = parse_tags(&entry.tags_text);
+                                        self.tag_suggestion = 0;
+
+                                        // Keep typing after the inserted tag
+                                        response.request_focus();
+                                        if let Some(mut state) = TextEdit::load_state(ui.ctx(), tags_id) {
+                                            let end = egui::text::CCursor::new(entry.tags_text.chars().count());
+                                            state.cursor.set_char_range(Some(egui::text::CCursorRange::one(end)));
+                                            state.store(ui.ctx(), tags_id);
+                                        }
+                                    }
+
+                                    // Same for workouts, one per line
+                                    let workouts_id = egui::Id::new(("workouts", entry.date));
+                                    if !ui.memory(|m| m.has_focus(workouts_id)) {
+                                        entry.workouts_text = entry.workouts.join("\n");
+                                    }
+                                    let response = ui.add(TextEdit::multiline(&mut entry.workouts_text)
+                                        .id(workouts_id)
+                                        .desired_rows(2)
+                                        .hint_text("Workouts, one per line"));
+                                    if response.changed() {
+                                        entry.workouts = entry.workouts_text.lines()
+                                            .map(|line| line.trim())
+                                            .filter(|line| line.len() > 0)
+                                            .map(|line| line.to_string())
+                                            .collect();
+                                    }
+
+                                    // Only the path is stored, the photo stays where it is
+                                    let mut removed_image = None;
+                                    for (index, path) in entry.image_paths.iter().enumerate() {
+                                        ui.horizontal(|ui| {
+                                            ui.label(format!("🖼 {}", path));
+                                            if ui.button("-").clicked() {
+                                                removed_image = Some(index);
+                                            }
+                                        });
+                                    }
+                                    if let Some(index) = removed_image {
+                                        entry.image_paths.remove(index);
+                                    }
+
+                                    if ui.button("Add photo").clicked() {
+                                        if let Some(path) = rfd::FileDialog::new()
+                                            .add_filter("Images", &["png", "jpg", "jpeg"])
+                                            .pick_file() {
+                                            entry.image_paths.push(path.display().to_string());
+                                        }
+                                    }
+
+                                    if ui.input(|i| i.key_pressed(egui::Key::Escape)) {
+                                        self.mode = Mode::Main;
+                                        entry.edit = false;
+                                        closed_date = Some(entry.date);
+                                    }
+                                } else if entry.has_data() {
+                                    ui.horizontal(|ui| {
+                                        ui.heading(date_string);
+
+                                        let mut weight_string = String::from("--");
+
+                                        if entry.weight_kg() != 0.0 {
+                                            weight_string = format!("{:.1}", entry.weight_kg() * self.units.weight_factor());
+                                        }
+                                        weight_string.push_str(self.units.weight_suffix());
+
+                                        let mut waist_string = String::from("--");
+                                        if entry.waist_cm() != 0.0 {
+                                            waist_string = format!("{:.1}", entry.waist_cm() * self.units.length_factor());
+                                        }
+                                        waist_string.push_str(self.units.length_suffix());
+
+                                        let mut mood_string = String::from("--");
+                                        if let Some(mood) = entry.mood {
+                                            mood_string = format!("{}", mood);
+                                        }
+                                        mood_string.push_str(" ★");
+
+                                        let mut sleep_string = String::from("--");
+                                        if entry.sleep_hours() != 0.0 {
+                                            sleep_string = format!("{:.1}", entry.sleep_hours());
+                                        }
+                                        sleep_string.push_str(" h");
+
+                                        let mut hr_string = String::from("--");
+                                        if let Some(resting_hr) = entry.resting_hr() {
+                                            hr_string = format!("{}", resting_hr);
+                                        }
+                                        hr_string.push_str(" bpm");
+
+                                        let mut calorie_string = String::from("--");
+                                        if let Some(calories) = entry.calories() {
+                                            calorie_string = format!("{}", calories);
+                                        }
+                                        calorie_string.push_str(" kcal");
+
+                                        ui.label(weight_string);
+                                        ui.label(waist_string);
+                                        ui.label(mood_string);
+                                        ui.label(sleep_string);
+                                        ui.label(hr_string);
+                                        ui.label(calorie_string);
+                                    });
+
+                                    if entry.is_hidden(&self.revealed) {
+                                        if ui.add(Label::new(RichText::new("[locked]").weak()).sense(Sense::click())).on_hover_text("Click to reveal").clicked() {
+                                            self.revealed.insert(entry.date);
+                                        }
+                                    } else if entry.content.len() > 0 {
+                                        ui.label(&entry.content);
+                                    }
+                                }
+
+                                ui.add_space(10.0);
+                            }
+
+                            // An untouched prompt or template doesn't count as writing anything,
+                            // only checked for the entry being closed so older ones keep theirs
+                            if let Some(date) = closed_date {
+                                let starter = self.starter_content(date);
+                                if let Some(entry) = self.entries.iter_mut().find(|entry| entry.date == date) {
+                                    if starter.trim().len() > 0 && entry.content.trim() == starter.trim() {
+                                        entry.content.clear();
+                                    }
+                                }
+                            }
+
+                            // Emptying an entry that had something in it asks first, like its "-" button,
+                            // the snapshot from when editing started tells what it had
+                            let emptied = closed_date.filter(|date| {
+                                self.entries.iter().any(|entry| entry.date == *date && !entry.has_data())
+                                    && self.undo_stack.last().is_some_and(|snapshot| {
+                                        snapshot.entries.iter().any(|before| before.date == *date && before.has_data())
+                                    })
+                            });
+                            if let Some(date) = emptied {
+                                if let Some(entry) = self.entries.iter_mut().find(|entry| entry.date == date) {
+                                    entry.edit = true;
+                                }
+                                self.mode = Mode::Edit;
+                                self.pending_delete = Some(PendingDelete::Entry(date));
+                            }
+
+                            self.entries.retain(|t| {t.edit == true || t.has_data()});
+                            self.sort_entries();
+
+                            if let Some(date) = moved_date {
+                                self.curr_date = date;
+                                self.scroll_to_date = Some(date);
+                            }
+                        },
+                    }
+                });
+            });
+        });
+
+        // Every edit session can be undone as a whole, this covers deleting
+        // entries, tasks and sections as they all happen in Mode::Edit
+        if !was_editing && matches!(self.mode, Mode::Edit) {
+            self.push_snapshot();
+        }
+
+        // Settings window
+        let mut show_settings = self.show_settings;
+        egui::Window::new("Settings")
+            .open(&mut show_settings)
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| self.settings_ui(ui));
+        self.show_settings = show_settings;
+
+        // Weekly review window
+        let mut show_review = self.show_review;
+        egui::Window::new("Weekly review")
+            .open(&mut show_review)
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| self.weekly_review_ui(ui));
+        self.show_review = show_review;
+
+        // Search and replace window
+        let mut show_replace = self.show_replace;
+        egui::Window::new("Replace")
+            .open(&mut show_replace)
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| self.replace_ui(ui));
+        self.show_replace = show_replace;
+
+        // Full size photo
+        if let Some(path) = self.viewing_image.clone() {
+            let modal = egui::Modal::new(egui::Id::new("photo")).show(ctx, |ui| {
+                ui.add(egui::Image::new(format!("file://{}", path)).max_size(ctx.screen_rect().size() * 0.8));
+
+                if ui.button("Close").clicked() {
+                    self.viewing_image = None;
+                }
+            });
+
+            if modal.should_close() {
+                self.viewing_image = None;
+            }
+        }
+
+        // Delete confirmation, modal so nothing can move underneath it
+        if let Some(pending_delete) = self.pending_delete {
+            let mut confirmed = false;
+            let mut cancelled = false;
+
+            let modal = egui::Modal::new(egui::Id::new("confirm_delete")).show(ctx, |ui| {
+                ui.heading(pending_delete.question());
+                ui.add_space(10.0);
+
+                ui.horizontal(|ui| {
+                    if ui.button("Yes").clicked() {
+                        confirmed = true;
                     }
+                    if ui.button("No").clicked() {
+                        cancelled = true;
+                    }
+                });
+            });
+
+            if confirmed {
+                self.confirm_delete(pending_delete);
+                self.pending_delete = None;
+            } else if cancelled || modal.should_close() {
+                self.pending_delete = None;
+            }
+        }
+
+        if self.show_help {
+            let modal = egui::Modal::new(egui::Id::new("help")).show(ctx, |ui| {
+                ui.heading("Keyboard shortcuts");
+                egui::Grid::new("shortcuts").striped(true).show(ui, |ui| {
+                    for (keys, action) in SHORTCUTS {
+                        ui.label(RichText::new(*keys).strong());
+                        ui.label(*action);
+                        ui.end_row();
+                    }
+                });
+
+                ui.add_space(10.0);
+                ui.heading("Clicking");
+                egui::Grid::new("clicks").striped(true).show(ui, |ui| {
+                    for (target, action) in CLICKS {
+                        ui.label(RichText::new(*target).strong());
+                        ui.label(*action);
+                        ui.end_row();
+                    }
+                });
+
+                ui.add_space(10.0);
+                if ui.button("Close").clicked() {
+                    self.show_help = false;
                 }
-            },
+            });
+
+            if modal.should_close() {
+                self.show_help = false;
+            }
+        }
+
+        if let Some(date) = self.pending_private_edit {
+            let mut confirmed = false;
+            let mut cancelled = false;
+
+            let modal = egui::Modal::new(egui::Id::new("confirm_private_edit")).show(ctx, |ui| {
+                ui.heading("This entry is private. Edit it anyway?");
+                ui.add_space(10.0);
+
+                ui.horizontal(|ui| {
+                    if ui.button("Yes").clicked() {
+                        confirmed = true;
+                    }
+                    if ui.button("No").clicked() {
+                        cancelled = true;
+                    }
+                });
+            });
+
+            if confirmed {
+                self.revealed.insert(date);
+                self.pending_private_edit = None;
+
+                if self.private_edit_focus {
+                    self.private_edit_focus = false;
+                    self.enter_focus_mode();
+                } else {
+                    // The undo snapshot for this frame's mode change has already been taken
+                    self.push_snapshot();
+                    self.edit_entry(date);
+                }
+            } else if cancelled || modal.should_close() {
+                self.pending_private_edit = None;
+                self.private_edit_focus = false;
+            }
+        }
+
+        // Writing an encrypted diary out in plain text, private entries included
+        if self.confirm_plain_export {
+            let mut confirmed = false;
+            let mut cancelled = false;
+
+            let modal = egui::Modal::new(egui::Id::new("confirm_export")).show(ctx, |ui| {
+                ui.heading("Export without encryption?");
+                ui.label("The diary is encrypted, but the export is plain JSON that anyone with the file can read, private entries included.");
+                ui.add_space(10.0);
+
+                ui.horizontal(|ui| {
+                    if ui.button("Export").clicked() {
+                        confirmed = true;
+                    }
+                    if ui.button("Cancel").clicked() {
+                        cancelled = true;
+                    }
+                });
+            });
+
+            if confirmed {
+                self.confirm_plain_export = false;
+                self.export_backup();
+            } else if cancelled || modal.should_close() {
+                self.confirm_plain_export = false;
+            }
+        }
+
+        // Importing a full backup, either on top of the diary or instead of it
+        if let Some(path) = self.pending_import.clone() {
+            let mut merge = None;
+            let mut cancelled = false;
+
+            let modal = egui::Modal::new(egui::Id::new("confirm_import")).show(ctx, |ui| {
+                ui.heading(format!("Import {}?", path));
+                ui.label("Replace swaps in the backup's entries, tasks and settings. Merge only adds the days and sections missing here.");
+                ui.add_space(10.0);
+
+                ui.horizontal(|ui| {
+                    if ui.button("Replace").clicked() {
+                        merge = Some(false);
+                    }
+                    if ui.button("Merge").clicked() {
+                        merge = Some(true);
+                    }
+                    if ui.button("Cancel").clicked() {
+                        cancelled = true;
+                    }
+                });
+            });
+
+            if let Some(merge) = merge {
+                self.pending_import = None;
+                self.backup_message = match self.import_all(&path, merge) {
+                    Ok(num_entries) if merge => format!("Added {} entries from {}", num_entries, path),
+                    Ok(num_entries) => format!("Replaced the diary with {} entries from {}", num_entries, path),
+                    Err(error) => format!("Import failed: {}", error),
+                };
+                ctx.set_theme(self.theme.preference());
+                self.toast = Some((self.backup_message.clone(), ctx.input(|i| i.time)));
+            } else if cancelled || modal.should_close() {
+                self.pending_import = None;
+            }
         }
 
-        PlotPoints::new(waist_points)
+        // Import results fade out on their own after a few seconds
+        if let Some((message, shown_at)) = self.toast.clone() {
+            let now = ctx.input(|i| i.time);
+            if now - shown_at > TOAST_SECONDS {
+                self.toast = None;
+            } else {
+                egui::Area::new(egui::Id::new("toast"))
+                    .anchor(egui::Align2::RIGHT_BOTTOM, egui::vec2(-12.0, -12.0))
+                    .show(ctx, |ui| {
+                        egui::Frame::popup(ui.style()).show(ui, |ui| {
+                            ui.label(message);
+                        });
+                    });
+                ctx.request_repaint_after(std::time::Duration::from_secs_f64(TOAST_SECONDS - (now - shown_at)));
+            }
+        }
     }
 
-    pub fn get_max_weight(&self) -> f32 {
-        let mut max_weight = 0.0;
+    fn save(&mut self, storage: &mut dyn eframe::Storage) {
+        if self.encrypted {
+            // Only the settings go in eframe's storage, the diary lives in the encrypted file
+            let entries = std::mem::take(&mut self.entries);
+            let sections = std::mem::take(&mut self.sections);
+            let archived_tasks = std::mem::take(&mut self.archived_tasks);
 
-        for entry in &self.entries {
-            if entry.weight_kg > max_weight {
-                max_weight = entry.weight_kg;
-            }
+            store_json(storage, self);
+
+            self.entries = entries;
+            self.sections = sections;
+            self.archived_tasks = archived_tasks;
+        } else {
+            store_json(storage, self);
         }
 
-        max_weight
+        // Don't overwrite the diary file with the empty locked state
+        if self.locked {
+            return;
+        }
+
+        self.save_error = self.rotate_backups()
+            .and_then(|_| self.save_to_file())
+            .err()
+            .map(|error| error.to_string());
     }
 
-    pub fn get_max_waist(&self) -> f32 {
-        let mut max_waist = 0.0;
+    fn auto_save_interval(&self) -> std::time::Duration {
+        // Clamped so a typo can't make it save every frame
+        std::time::Duration::from_secs(self.autosave_secs.max(5))
+    }
+}
 
-        for entry in &self.entries {
-            if entry.waist_cm > max_waist {
-                max_waist = entry.waist_cm;
-            }
-        }
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-        max_waist
+    fn date(year: i32, month: u8, day: u8) -> Date {
+        Date::from_calendar_date(year, Month::try_from(month).unwrap(), day).unwrap()
     }
 
-    pub fn add_section(&mut self, title: &str, edit: bool) {
-        self.sections.push(Section {title: title.to_string(), tasks: vec![], edit, delete: false});
+    fn weighed_entry(date: Date, weight_kg: f32) -> Entry {
+        let mut entry = Entry::new(date);
+        entry.set_value(WEIGHT, weight_kg);
+        entry
     }
 
-    pub fn clean_tasks(&mut self) {
-        for section in &mut self.sections {
-            section.tasks.retain(|t| t.done != true);
-
-            if section.tasks.len() == 0 {
-                section.delete = true;
-            }
-        }
+    fn app_on(curr_date: Date) -> MyApp {
+        let mut app = MyApp::default();
+        app.curr_date = curr_date;
+        app.today = curr_date;
+        app
+    }
 
-        self.sections.retain(|t| t.delete != true);
+    fn write_temp_csv(name: &str, contents: &str) -> String {
+        let path = std::env::temp_dir().join(format!("rust_diary_{}_{}.csv", std::process::id(), name));
+        fs::write(&path, contents).unwrap();
+        path.to_str().unwrap().to_string()
     }
-}
 
-fn x_axis_dates(grid_mark: GridMark, _: &RangeInclusive<f64>) -> String {
-    let curr_date_julian = OffsetDateTime::now_local().unwrap().date().to_julian_day();
-    let grid_date_julian = curr_date_julian + grid_mark.value.round() as i32;
-    let grid_date = Date::from_julian_day(grid_date_julian).unwrap();
-    let format = format_description::parse("[day]/[month]").unwrap();
-    let date_string = grid_date.format(&format).unwrap();
+    #[test]
+    fn auto_y_bounds_pads_the_range() {
+        let (min, max) = auto_y_bounds(&PlotPoints::new(vec![[0.0, 70.0], [1.0, 90.0]]), (0.0, 1.0));
+        assert!((min - 69.0).abs() < 1e-9);
+        assert!((max - 91.0).abs() < 1e-9);
 
-    date_string
-}
+        // A flat series still gets some height
+        let (min, max) = auto_y_bounds(&PlotPoints::new(vec![[0.0, 80.0], [1.0, 80.0]]), (0.0, 1.0));
+        assert_eq!((min, max), (79.5, 80.5));
+    }
 
-impl eframe::App for MyApp {
-    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
-        // Check date
-        if OffsetDateTime::now_local().unwrap().date() > self.curr_date {
-            self.curr_date = OffsetDateTime::now_local().unwrap().date();
-        }
+    #[test]
+    fn auto_y_bounds_falls_back_without_enough_points() {
+        assert_eq!(auto_y_bounds(&PlotPoints::new(vec![[0.0, 80.0]]), (10.0, 20.0)), (10.0, 20.0));
+        assert_eq!(auto_y_bounds(&PlotPoints::new(vec![[0.0, f64::NAN], [1.0, 80.0]]), (10.0, 20.0)), (10.0, 20.0));
+    }
 
-        egui::SidePanel::right("ToDo").show(ctx, |ui| {
-            // ToDo section
-            egui::ScrollArea::vertical().show(ui, |ui| {
-                ui.vertical(|ui| {
-                    match self.mode {
-                        // In this mode you can click a Task or a Section to edit it,
-                        // the checkboxes to mark tasks as completed and add new Tasks
-                        // and Sections
-                        Mode::Main => {
-                            // Handle zooming
-                            if ui.input(|i| i.key_pressed(egui::Key::ArrowUp)) {
-                                self.scale_factor += 0.2;
+    #[test]
+    fn project_goal_date_follows_the_trend() {
+        let mut app = app_on(date(2024, 1, 10));
+        app.entries = vec![
+            weighed_entry(date(2024, 1, 10), 78.0),
+            weighed_entry(date(2024, 1, 9), 79.0),
+            weighed_entry(date(2024, 1, 8), 80.0),
+        ];
 
-                                if self.scale_factor > 3.0 {
-                                    self.scale_factor = 3.0;
-                                }
+        app.goal_weight_kg = Some(70.0);
+        assert_eq!(app.project_goal_date(), Some(date(2024, 1, 18)));
 
-                                ctx.set_pixels_per_point(self.scale_factor);
-                            }
-                            if ui.input(|i| i.key_pressed(egui::Key::ArrowDown)) {
-                                self.scale_factor -= 0.2;
+        // Heading away from the goal never gets there
+        app.goal_weight_kg = Some(90.0);
+        assert_eq!(app.project_goal_date(), None);
+    }
 
-                                if self.scale_factor < 1.0 {
-                                    self.scale_factor = 1.0;
-                                }
+    #[test]
+    fn project_goal_date_needs_three_readings() {
+        let mut app = app_on(date(2024, 1, 10));
+        app.goal_weight_kg = Some(70.0);
+        app.entries = vec![
+            weighed_entry(date(2024, 1, 10), 78.0),
+            weighed_entry(date(2024, 1, 9), 79.0),
+        ];
 
-                                ctx.set_pixels_per_point(self.scale_factor);
-                            }
+        assert_eq!(app.project_goal_date(), None);
+    }
 
-                            // Handle graph zoom
-                            if ui.input(|i| i.key_pressed(egui::Key::ArrowLeft)) {
-                                self.zoom = Zoom::Day;
-                            }
+    #[test]
+    fn parse_measurement_treats_blank_as_zero() {
+        assert_eq!(parse_measurement(None), Ok(0.0));
+        assert_eq!(parse_measurement(Some("  ")), Ok(0.0));
+        assert_eq!(parse_measurement(Some(" 72.5 ")), Ok(72.5));
+        assert!(parse_measurement(Some("abc")).is_err());
+    }
 
-                            if ui.input(|i| i.key_pressed(egui::Key::ArrowRight)) {
-                                self.zoom = Zoom::Week;
-                            }
+    #[test]
+    fn import_csv_skips_header_and_bad_rows() {
+        let path = write_temp_csv("import", "date,weight,waist,content\n2024-01-02,80.5,90,Hello\nbad,1,2,x\n2024-01-03,abc,,\n2024-01-04,,85,\n");
+        let mut app = app_on(date(2024, 1, 10));
 
-                            if ui.input(|i| i.key_pressed(egui::Key::C)) {
-                                self.clean_tasks();
-                            }
+        let num_imported = app.import_csv(&path).unwrap();
+        fs::remove_file(&path).unwrap();
 
-                            for section in &mut self.sections {
-                                // Render Section title as clickable, if clicked edit it
-                                if ui.add(Label::new(RichText::new(&section.title).heading()).sense(Sense::click())).clicked() {
-                                    // Enter edit section mode
-                                    section.edit = true;
-                                    self.mode = Mode::Edit;
-                                }
+        assert_eq!(num_imported, 2);
+        assert_eq!(app.import_errors.len(), 2);
+        assert_eq!(app.entries.iter().map(|entry| entry.date).collect::<Vec<_>>(), [date(2024, 1, 4), date(2024, 1, 2)]);
 
-                                // Render Tasks as clickable, if clicked edit it
-                                for task in &mut section.tasks {
-                                    ui.horizontal(|ui| {
-                                        ui.checkbox(&mut task.done, "");
-                                        if ui.add(Label::new(&task.text).sense(Sense::click())).clicked() {
-                                            task.edit = true;
-                                            self.mode = Mode::Edit;
-                                            self.first_time_edit = true;
-                                        }
-                                    });
-                                }
+        assert_eq!(app.entries[0].value(WEIGHT), None);
+        assert_eq!(app.entries[0].waist_cm(), 85.0);
+        assert_eq!(app.entries[1].weight_kg(), 80.5);
+        assert_eq!(app.entries[1].waist_cm(), 90.0);
+        assert_eq!(app.entries[1].content, "Hello");
+    }
 
-                                // Render an invisible Task used to add a Task
-                                let response = ui.add(Label::new("                             "));
-                                if response.clicked() {
-                                    let empty = String::new();
-                                    section.add_task(&empty, true);
-                                    self.mode = Mode::Edit;
-                                    self.first_time_edit = true;
-                                }
-                            }
-                            ui.separator();
+    #[test]
+    fn recurrence_is_due() {
+        // 2024-01-01 is a Monday
+        assert!(Recurrence::Daily.is_due(date(2024, 1, 1), date(2024, 1, 2)));
+        assert!(!Recurrence::Daily.is_due(date(2024, 1, 2), date(2024, 1, 2)));
 
-                            // Render an invisible Section used to add a Section
-                            let response = ui.add(Label::new(RichText::new("                             ").heading()));
-                            if response.clicked() {
-                                let empty = String::new();
-                                self.add_section(&empty, true);
-                                self.mode = Mode::Edit;
-                                self.first_time_edit = true;
-                            }
-                        },
+        assert!(!Recurrence::Weekly.is_due(date(2024, 1, 1), date(2024, 1, 7)));
+        assert!(Recurrence::Weekly.is_due(date(2024, 1, 1), date(2024, 1, 8)));
+        assert!(Recurrence::Weekly.is_due(date(2024, 1, 7), date(2024, 1, 8)));
 
-                        Mode::Edit => {
-                            for section in &mut self.sections {
-                                if section.edit {
-                                    ui.horizontal(|ui| {
-                                        let response = ui.add(TextEdit::singleline(&mut section.title));
+        assert!(!Recurrence::Weekdays.is_due(date(2024, 1, 5), date(2024, 1, 7)));
+        assert!(Recurrence::Weekdays.is_due(date(2024, 1, 5), date(2024, 1, 8)));
+        assert!(Recurrence::Weekdays.is_due(date(2024, 1, 1), date(2024, 1, 2)));
+    }
 
-                                        if self.first_time_edit {
-                                            response.request_focus();
-                                            self.first_time_edit = false;
-                                        }
+    #[test]
+    fn stats_for_range_counts_days_with_writing() {
+        let mut app = app_on(date(2024, 1, 10));
+        let mut written = weighed_entry(date(2024, 1, 9), 80.0);
+        written.content = String::from("Wrote something");
+        let mut blank = weighed_entry(date(2024, 1, 8), 82.0);
+        blank.content = String::from("  \n");
+        app.entries = vec![written, blank, weighed_entry(date(2023, 12, 31), 90.0)];
 
-                                        if ui.input(|i| i.key_pressed(egui::Key::Enter) || i.key_pressed(egui::Key::Escape)) {
-                                            self.mode = Mode::Main;
-                                            section.edit = false;
-                                        }
+        let stats = app.stats_for_range(date(2024, 1, 1), date(2024, 1, 10));
 
-                                        if ui.button("-").clicked() {
-                                            self.mode = Mode::Main;
-                                            section.tasks.clear();
-                                            section.delete = true;
-                                        }
-                                    });
-                                } else {
-                                    ui.heading(&section.title);
-                                }
+        // Measurements alone aren't journaling, but still count towards the averages
+        assert_eq!(stats.days_journaled, 1);
+        assert_eq!(stats.weight_kg.map(|weight_kg| weight_kg.average), Some(81.0));
+    }
 
-                                for task in &mut section.tasks {
-                                    ui.horizontal(|ui| {
-                                        ui.checkbox(&mut task.done, "");
+    #[test]
+    fn shift_month_wraps_years() {
+        assert_eq!(shift_month(2024, Month::January, -1), (2023, Month::December));
+        assert_eq!(shift_month(2024, Month::December, 1), (2025, Month::January));
+        assert_eq!(shift_month(2024, Month::March, -14), (2023, Month::January));
+        assert_eq!(shift_month(2024, Month::February, 0), (2024, Month::February));
+    }
 
-                                        if task.edit {
-                                            // Render edit text box for task
-                                            let response = ui.add(TextEdit::singleline(&mut task.text));
+    #[test]
+    fn current_streak_counts_back_from_today() {
+        let mut app = app_on(date(2024, 1, 10));
+        for day in [9, 8, 7, 5] {
+            let mut entry = Entry::new(date(2024, 1, day));
+            entry.content = String::from("Wrote something");
+            app.insert_entry(entry);
+        }
+        app.insert_entry(weighed_entry(date(2024, 1, 6), 80.0));
 
-                                            if self.first_time_edit {
-                                                response.request_focus();
-                                                self.first_time_edit = false;
-                                            }
+        // Nothing written today yet still keeps yesterday's streak
+        assert_eq!(app.current_streak(), 3);
 
-                                            if ui.input(|i| i.key_pressed(egui::Key::Enter) || i.key_pressed(egui::Key::Escape)) {
-                                                self.mode = Mode::Main;
-                                                task.edit = false;
-                                            }
+        let mut entry = Entry::new(date(2024, 1, 10));
+        entry.content = String::from("Today");
+        app.insert_entry(entry);
+        assert_eq!(app.current_streak(), 4);
+    }
 
-                                            if ui.button("-").clicked() {
-                                                self.mode = Mode::Main;
-                                                task.delete = true;
-                                            }
-                                        } else {
-                                            // Render normally
-                                            ui.label(&task.text);
-                                        }
-                                    });
-                                }
+    #[test]
+    fn replace_ignore_case_matches_any_case() {
+        assert_eq!(replace_ignore_case("Hello hello HELLO", "hello", "hi"), "hi hi hi");
+        assert_eq!(replace_ignore_case("Ärger und ärger", "ÄRGER", "Wut"), "Wut und Wut");
+        assert_eq!(replace_ignore_case("no match", "xyz", "hi"), "no match");
+        assert_eq!(replace_ignore_case("unchanged", "", "hi"), "unchanged");
+    }
 
-                                ui.add_space(12.0);
+    #[test]
+    fn goal_progress_works_both_ways() {
+        let mut app = app_on(date(2024, 1, 10));
+        assert_eq!(app.goal_progress(), None);
 
-                                section.tasks.retain(|t| t.delete != true);
-                            }
+        app.entries = vec![
+            weighed_entry(date(2024, 1, 10), 78.0),
+            weighed_entry(date(2024, 1, 1), 80.0),
+        ];
+        app.goal_weight_kg = Some(70.0);
+        assert!((app.goal_progress().unwrap() - 0.2).abs() < 1e-6);
 
-                            self.sections.retain(|t| t.delete != true);
+        app.entries = vec![
+            weighed_entry(date(2024, 1, 10), 65.0),
+            weighed_entry(date(2024, 1, 1), 60.0),
+        ];
+        assert!((app.goal_progress().unwrap() - 0.5).abs() < 1e-6);
 
-                            ui.separator();
-                        },
-                    }
-                });
-            });
-        });
+        // Overshooting the goal stops at full
+        app.goal_weight_kg = Some(62.0);
+        assert_eq!(app.goal_progress(), Some(1.0));
+    }
 
-        // Diary section
-        egui::CentralPanel::default().show(ctx, |ui| {
-            ui.vertical(|ui| {
+    #[test]
+    fn short_date_format_drops_the_year() {
+        assert_eq!(short_date_format("[day]-[month]-[year]"), "[day]-[month]");
+        assert_eq!(short_date_format("[year]-[month]-[day]"), "[month]-[day]");
+        assert_eq!(short_date_format("[year repr:last_two]/[month]"), "[month]");
+        assert_eq!(short_date_format("[day]/[month]"), "[day]/[month]");
+        assert_eq!(short_date_format("[year]"), "[year]");
+    }
 
-                // Section with graphs
-                ui.horizontal(|ui| {
-                    let weight_points = self.get_weights();
-                    let waist_points = self.get_waists();
+    #[test]
+    fn migrate_fills_missing_flags() {
+        let value = migrate(serde_json::json!({
+            "entries": [],
+            "sections": [{"title": "Todo", "tasks": [{"text": "Task"}]}]
+        }));
 
-                    let weight_line = Line::new("Weight", weight_points)
-                        .width(1.5)
-                        .color(Color32::CYAN);
-                    let waist_line = Line::new("Waist", waist_points)
-                        .width(1.5)
-                        .color(Color32::CYAN);
-
-                    let half_ui = ui.available_width() / 2.0 - 20.0;
-
-                    let max_weight = self.get_max_weight();
-                    let max_waist = self.get_max_waist();
-
-                    let max_weight = ((max_weight.floor() as i32 / 5 + 1) * 5) as f64;
-                    let max_waist = ((max_waist.floor() as i32 / 5 + 1) * 5) as f64;
-
-                    Plot::new("weight").view_aspect(1.6)
-                        .width(half_ui)
-                        .allow_boxed_zoom(false)
-                        .allow_double_click_reset(false)
-                        .allow_drag(false)
-                        .allow_scroll(false)
-                        .allow_zoom(false)
-                        .show_x(false)
-                        .default_y_bounds(max_weight - 20.0, max_weight)
-                        .show_background(false)
-                        .x_axis_formatter(x_axis_dates)
-                        .y_axis_label("Weight [kg]")
-                        .show(ui, |plot_ui| plot_ui.line(weight_line));
-                    Plot::new("waist").view_aspect(1.6)
-                        .width(half_ui)
-                        .allow_boxed_zoom(false)
-                        .allow_double_click_reset(false)
-                        .allow_drag(false)
-                        .allow_scroll(false)
-                        .allow_zoom(false)
-                        .show_x(false)
-                        .default_y_bounds(max_waist - 20.0, max_waist)
-                        .show_background(false)
-                        .x_axis_formatter(x_axis_dates)
-                        .y_axis_label("Waist [cm]")
-                        .show(ui, |plot_ui| plot_ui.line(waist_line));
-                });
+        assert_eq!(value["schema_version"], SCHEMA_VERSION);
+        assert_eq!(value["first_time_edit"], false);
+        assert_eq!(value["scale_factor"], 2.0);
+        assert_eq!(value["sections"][0]["edit"], false);
+        assert_eq!(value["sections"][0]["tasks"][0]["done"], false);
+    }
 
-                // Section with diary entries
-                egui::ScrollArea::vertical().show(ui, |ui| {
-                    // If there is no entry for today, add a prompt for it
-                    if let None = self.get_entry_by_date(self.curr_date) {
-                        let format = format_description::parse("[day]-[month]-[year]").unwrap();
-                        let date_string = self.curr_date.format(&format).unwrap();
-                        ui.heading(date_string);
-                        if ui.add(Label::new("Add entry for today!").sense(Sense::click())).clicked() {
-                            let new_entry = Entry {
-                                content: String::new(),
-                                weight_kg: 0.0,
-                                waist_cm: 0.0,
-                                date: self.curr_date,
-                                edit: true,
-                            };
+    #[test]
+    fn migrate_leaves_current_files_alone() {
+        let value = migrate(serde_json::json!({
+            "schema_version": SCHEMA_VERSION,
+            "entries": [{"content": "", "values": {"Weight": 70.0}}]
+        }));
 
-                            self.entries.insert(0, new_entry);
+        assert!(value.get("first_time_edit").is_none());
+        assert_eq!(value["entries"][0]["values"], serde_json::json!({"Weight": 70.0}));
+    }
 
-                            self.mode = Mode::Edit;
-                            self.first_time_edit = true;
-                        }
+    #[test]
+    fn import_health_csv_keeps_the_last_reading_of_a_day() {
+        let path = write_temp_csv("health", "Date,Weight (kg)\n2024-01-02T08:00:00Z,80.0\n2024-01-02T20:00:00Z,81.0\n2024-01-05T08:00:00Z,79.5\nnot a date,70\n");
+        let mut app = app_on(date(2024, 1, 10));
+        let mut existing = Entry::new(date(2024, 1, 5));
+        existing.content = String::from("Kept");
+        app.insert_entry(existing);
 
-                        ui.add_space(10.0);
-                    }
+        let (num_days, range) = app.import_health_csv(&path).unwrap();
+        fs::remove_file(&path).unwrap();
 
-                    // Rest of entries
-                    match self.mode {
-                        Mode::Main => {
-                            // Toggle redux mode
-                            if ui.input(|i| i.key_pressed(egui::Key::R)) {
-                                self.redux_mode = !self.redux_mode;
-                            }
+        assert_eq!(num_days, 2);
+        assert_eq!(range, Some((date(2024, 1, 2), date(2024, 1, 5))));
+        assert_eq!(app.import_errors.len(), 1);
+        assert_eq!(app.entries.len(), 2);
+        assert_eq!(app.entries[0].content, "Kept");
+        assert_eq!(app.entries[0].weight_kg(), 79.5);
+        assert_eq!(app.entries[1].weight_kg(), 81.0);
 
-                            for entry in &mut self.entries {
-                                let format = format_description::parse("[day]-[month]-[year]").unwrap();
-                                let date_string = entry.date.format(&format).unwrap();
+        // The whole import undoes in one step
+        assert_eq!(app.undo_stack.len(), 1);
+        app.undo();
+        assert_eq!(app.entries.len(), 1);
+        assert_eq!(app.entries[0].value(WEIGHT), None);
+    }
 
-                                if !self.redux_mode || entry.content.len() > 0 {
-                                    ui.horizontal(|ui| {
-                                        let mut weight_string = String::from("--");
+    #[test]
+    fn week_start_of_honours_the_setting() {
+        let mut app = app_on(date(2024, 1, 10));
 
-                                        if entry.weight_kg != 0.0 {
-                                            weight_string = format!("{:.1}", entry.weight_kg);
-                                        }
-                                        weight_string.push_str(" kg");
+        assert_eq!(app.week_start_of(date(2024, 1, 3)), date(2024, 1, 1));
+        assert_eq!(app.week_start_of(date(2024, 1, 1)), date(2024, 1, 1));
 
-                                        let mut waist_string = String::from("--");
-                                        if entry.waist_cm != 0.0 {
-                                            waist_string = format!("{:.1}", entry.waist_cm);
-                                        }
-                                        waist_string.push_str(" cm");
+        app.week_start = Weekday::Sunday;
+        assert_eq!(app.week_start_of(date(2024, 1, 3)), date(2023, 12, 31));
+        assert_eq!(app.week_start_of(date(2023, 12, 31)), date(2023, 12, 31));
+    }
 
-                                        if ui.add(Label::new(RichText::new(date_string).heading()).sense(Sense::click())).clicked() {
-                                            entry.edit = true;
-                                            self.mode = Mode::Edit;
-                                            self.first_time_edit = true;
-                                        }
-                                        ui.label(weight_string);
-                                        ui.label(waist_string);
-                                    });
-                                }
+    #[test]
+    fn merge_duplicates_folds_entries_of_a_day() {
+        let mut app = app_on(date(2024, 1, 10));
 
-                                if entry.content.len() > 0 {
-                                    if ui.add(Label::new(&entry.content).sense(Sense::click())).clicked() {
-                                        entry.edit = true;
-                                        self.mode = Mode::Edit;
-                                        self.first_time_edit = true;
-                                    }
-                                    ui.add_space(10.0);
-                                }
-                            }
-                        },
+        let mut first = weighed_entry(date(2024, 1, 1), 80.0);
+        first.content = String::from("a");
+        first.tags = vec![String::from("x")];
+        let mut second = weighed_entry(date(2024, 1, 1), 81.0);
+        second.content = String::from("b");
+        second.set_value(WAIST, 90.0);
+        second.tags = vec![String::from("x"), String::from("y")];
 
-                        Mode::Edit => {
-                            for entry in &mut self.entries {
-                                let format = format_description::parse("[day]-[month]-[year]").unwrap();
-                                let date_string = entry.date.format(&format).unwrap();
+        app.entries = vec![first, Entry::new(date(2024, 1, 2)), second, Entry::new(date(2024, 1, 1))];
+        assert_eq!(app.duplicate_dates(), [date(2024, 1, 1)]);
 
-                                if entry.edit {
-                                    ui.horizontal(|ui| {
-                                        ui.heading(date_string);
+        app.merge_duplicates();
 
-                                        ui.add(DragValue::new(&mut entry.weight_kg).speed(0.1));
-                                        ui.label(" kg");
-                                        ui.add(DragValue::new(&mut entry.waist_cm).speed(0.1));
-                                        ui.label(" cm");
-                                    });
+        assert!(app.duplicate_dates().is_empty());
+        assert_eq!(app.entries.len(), 2);
+        assert_eq!(app.undo_stack.len(), 1);
 
-                                    let response = ui.add_sized([ui.available_width(), 1.0], TextEdit::multiline(&mut entry.content));
+        let merged = app.entries.iter().find(|entry| entry.date == date(2024, 1, 1)).unwrap();
+        assert_eq!(merged.content, "a\n\nb");
+        assert_eq!(merged.weight_kg(), 81.0);
+        assert_eq!(merged.waist_cm(), 90.0);
+        assert_eq!(merged.tags, ["x", "y"]);
+    }
 
-                                    if self.first_time_edit {
-                                        response.request_focus();
-                                        self.first_time_edit = false;
-                                    }
+    #[test]
+    fn new_entries_start_from_the_template() {
+        let mut app = app_on(date(2024, 1, 10));
+        app.entry_template = String::from("Workout:\nMeals:");
 
-                                    if ui.input(|i| i.key_pressed(egui::Key::Escape)) {
-                                        self.mode = Mode::Main;
-                                        entry.edit = false;
-                                    }
-                                } else if entry.content.len() > 0 || entry.weight_kg > 0.0 || entry.waist_cm > 0.0 {
-                                    ui.horizontal(|ui| {
-                                        ui.heading(date_string);
+        app.edit_entry(date(2024, 1, 10));
 
-                                        let mut weight_string = String::from("--");
+        assert_eq!(app.entries.len(), 1);
+        assert_eq!(app.entries[0].content, "Workout:\nMeals:");
+        assert!(app.entries[0].edit);
+    }
 
-                                        if entry.weight_kg != 0.0 {
-                                            weight_string = format!("{:.1}", entry.weight_kg);
-                                        }
-                                        weight_string.push_str(" kg");
+    #[test]
+    fn starter_content_rotates_prompts_before_the_template() {
+        let mut app = app_on(date(2024, 1, 10));
+        assert_eq!(app.starter_content(date(2024, 1, 10)), "");
 
-                                        let mut waist_string = String::from("--");
-                                        if entry.waist_cm != 0.0 {
-                                            waist_string = format!("{:.1}", entry.waist_cm);
-                                        }
-                                        waist_string.push_str(" cm");
+        app.entry_template = String::from("Template");
+        assert_eq!(app.starter_content(date(2024, 1, 10)), "Template");
 
-                                        ui.label(weight_string);
-                                        ui.label(waist_string);
-                                    });
+        // Blank prompts are skipped and consecutive days get different ones
+        app.prompts = vec![String::from("  "), String::from(" First "), String::from("Second")];
+        let today = app.starter_content(date(2024, 1, 10));
+        let tomorrow = app.starter_content(date(2024, 1, 11));
+        assert_ne!(today, tomorrow);
+        for content in [today, tomorrow] {
+            assert!(content == "First\n\nTemplate" || content == "Second\n\nTemplate");
+        }
 
-                                    if entry.content.len() > 0 {
-                                        ui.label(&entry.content);
-                                    }
-                                }
+        app.entry_template.clear();
+        assert!(["First", "Second"].contains(&app.starter_content(date(2024, 1, 10)).as_str()));
+    }
 
-                                ui.add_space(10.0);
-                            }
+    #[test]
+    fn migrate_moves_readings_into_values() {
+        let value = migrate(serde_json::json!({
+            "schema_version": 1,
+            "entries": [{"content": "", "weight_kg": 80.0, "waist_cm": 0.0, "sleep_hours": null, "resting_hr": 55}]
+        }));
 
-                            self.entries.retain(|t| {t.edit == true || t.content.len() > 0 || t.weight_kg > 0.0 || t.waist_cm > 0.0});
-                        },
-                    }
-                });
-            });
-        });
+        assert_eq!(value["schema_version"], SCHEMA_VERSION);
+
+        let entry = &value["entries"][0];
+        assert_eq!(entry["values"], serde_json::json!({"Weight": 80.0, "Resting HR": 55.0}));
+        assert!(entry.get("weight_kg").is_none());
+        assert!(entry.get("waist_cm").is_none());
     }
 
-    fn save(&mut self, storage: &mut dyn eframe::Storage) {
-        eframe::set_value(storage, eframe::APP_KEY, self);
+    #[test]
+    fn leaving_focus_mode_untouched_leaves_no_entry() {
+        let mut app = app_on(date(2024, 1, 10));
+        app.entry_template = String::from("Template");
+
+        app.toggle_focus_mode();
+        assert_eq!(app.entries.len(), 1);
+        app.toggle_focus_mode();
+        assert!(app.entries.is_empty());
+
+        app.toggle_focus_mode();
+        app.entries[0].content.push_str("\n\nWrote something");
+        app.toggle_focus_mode();
+        assert_eq!(app.entries.len(), 1);
     }
 
-    fn auto_save_interval(&self) -> std::time::Duration {
-        std::time::Duration::from_secs(30)
+    #[test]
+    fn entries_per_month_counts_days_with_any_data() {
+        let mut app = app_on(date(2024, 3, 15));
+        let mut sleep_only = Entry::new(date(2024, 3, 2));
+        sleep_only.set_value(SLEEP, 7.5);
+        app.entries = vec![
+            weighed_entry(date(2024, 3, 1), 80.0),
+            weighed_entry(date(2024, 3, 1), 81.0),
+            sleep_only,
+            Entry::new(date(2024, 3, 3)),
+            weighed_entry(date(2024, 2, 10), 80.0),
+        ];
+
+        let months = app.get_entries_per_month();
+
+        assert_eq!(months.len(), 12);
+        assert_eq!(months[11], (String::from("Mar 24"), 2));
+        assert_eq!(months[10], (String::from("Feb 24"), 1));
     }
 }
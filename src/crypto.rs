@@ -0,0 +1,73 @@
+use std::io;
+
+use argon2::Argon2;
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::aead::rand_core::RngCore;
+
+// Encrypted files are MAGIC + salt + nonce + ciphertext
+const MAGIC: &[u8] = b"RDIARY1";
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+
+fn derive_key(password: &str, salt: &[u8]) -> io::Result<Key> {
+    let mut key = [0u8; 32];
+
+    Argon2::default()
+        .hash_password_into(password.as_bytes(), salt, &mut key)
+        .map_err(|error| io::Error::other(error.to_string()))?;
+
+    Ok(*Key::from_slice(&key))
+}
+
+// Argon2 is slow on purpose, so autosaves reuse one key and salt and only the nonce changes
+pub struct DerivedKey {
+    salt: [u8; SALT_LEN],
+    key: Key,
+}
+
+impl DerivedKey {
+    pub fn new(password: &str) -> io::Result<Self> {
+        let mut salt = [0u8; SALT_LEN];
+        OsRng.fill_bytes(&mut salt);
+
+        let key = derive_key(password, &salt)?;
+
+        Ok(DerivedKey { salt, key })
+    }
+}
+
+pub fn encrypt(data: &[u8], key: &DerivedKey) -> io::Result<Vec<u8>> {
+    let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+    let ciphertext = ChaCha20Poly1305::new(&key.key)
+        .encrypt(&nonce, data)
+        .map_err(|_| io::Error::other("Could not encrypt the diary"))?;
+
+    let mut output = Vec::with_capacity(MAGIC.len() + SALT_LEN + NONCE_LEN + ciphertext.len());
+    output.extend_from_slice(MAGIC);
+    output.extend_from_slice(&key.salt);
+    output.extend_from_slice(&nonce);
+    output.extend_from_slice(&ciphertext);
+
+    Ok(output)
+}
+
+pub fn is_encrypted(data: &[u8]) -> bool {
+    data.starts_with(MAGIC)
+}
+
+pub fn decrypt(data: &[u8], password: &str) -> io::Result<Vec<u8>> {
+    if !data.starts_with(MAGIC) || data.len() < MAGIC.len() + SALT_LEN + NONCE_LEN {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "Not an encrypted diary file"));
+    }
+
+    let (salt, rest) = data[MAGIC.len()..].split_at(SALT_LEN);
+    let (nonce, ciphertext) = rest.split_at(NONCE_LEN);
+
+    let key = derive_key(password, salt)?;
+
+    // The authentication tag fails to verify when the password is wrong
+    ChaCha20Poly1305::new(&key)
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "Wrong password"))
+}
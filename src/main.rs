@@ -1,9 +1,15 @@
 mod app;
+mod crypto;
+mod raster;
 
 use crate::app::MyApp;
 
 fn main() {
-    let native_options = eframe::NativeOptions::default();
+    // Window geometry is saved with the rest of the app state instead
+    let native_options = eframe::NativeOptions {
+        persist_window: false,
+        ..Default::default()
+    };
 
     let _ = eframe::run_native("Diary",  native_options, Box::new(|cc| Ok(Box::new(MyApp::new(cc)))));
 }
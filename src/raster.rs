@@ -0,0 +1,175 @@
+use std::collections::HashMap;
+use std::ops::Range;
+
+use eframe::egui::{Color32, TextureId};
+use eframe::egui::epaint::{ClippedPrimitive, ImageData, Primitive, TexturesDelta, Vertex};
+
+// A texture handed out by an offscreen context, premultiplied like everything egui paints
+pub struct Texture {
+    size: [usize; 2],
+    pixels: Vec<Color32>,
+}
+
+impl Texture {
+    // Text is rasterized at the output scale, so the nearest texel is the right one
+    fn sample(&self, u: f32, v: f32) -> Color32 {
+        let x = ((u * self.size[0] as f32) as usize).min(self.size[0].saturating_sub(1));
+        let y = ((v * self.size[1] as f32) as usize).min(self.size[1].saturating_sub(1));
+
+        self.pixels.get(y * self.size[0] + x).copied().unwrap_or(Color32::TRANSPARENT)
+    }
+}
+
+pub fn update_textures(textures: &mut HashMap<TextureId, Texture>, delta: &TexturesDelta) {
+    for (id, image_delta) in &delta.set {
+        let update = match &image_delta.image {
+            ImageData::Color(image) => Texture { size: image.size, pixels: image.pixels.clone() },
+            ImageData::Font(font) => Texture { size: font.size, pixels: font.srgba_pixels(None).collect() },
+        };
+
+        let Some([x, y]) = image_delta.pos else {
+            textures.insert(*id, update);
+            continue;
+        };
+
+        // Partial updates patch a region of a texture sent earlier, like new glyphs in the font atlas
+        if let Some(texture) = textures.get_mut(id) {
+            for row in 0..update.size[1].min(texture.size[1].saturating_sub(y)) {
+                for column in 0..update.size[0].min(texture.size[0].saturating_sub(x)) {
+                    texture.pixels[(y + row) * texture.size[0] + x + column] = update.pixels[row * update.size[0] + column];
+                }
+            }
+        }
+    }
+
+    for id in &delta.free {
+        textures.remove(id);
+    }
+}
+
+// Fills in tessellated meshes the way egui's own painters do, blending premultiplied colors in gamma space
+pub fn rasterize(primitives: &[ClippedPrimitive], textures: &HashMap<TextureId, Texture>, size: [u32; 2], pixels_per_point: f32) -> image::RgbaImage {
+    let [width, height] = size.map(|side| side as usize);
+    let mut pixels = vec![[0.0f32; 4]; width * height];
+
+    for primitive in primitives {
+        // Callbacks are only used by custom GPU painting, which a chart never does
+        let Primitive::Mesh(mesh) = &primitive.primitive else {
+            continue;
+        };
+        let Some(texture) = textures.get(&mesh.texture_id) else {
+            continue;
+        };
+
+        let clip = primitive.clip_rect;
+        let clip_x = (clip.min.x * pixels_per_point).round().max(0.0) as usize..((clip.max.x * pixels_per_point).round().max(0.0) as usize).min(width);
+        let clip_y = (clip.min.y * pixels_per_point).round().max(0.0) as usize..((clip.max.y * pixels_per_point).round().max(0.0) as usize).min(height);
+
+        for triangle in mesh.indices.chunks_exact(3) {
+            let vertices = [triangle[0], triangle[1], triangle[2]].map(|index| &mesh.vertices[index as usize]);
+            fill_triangle(&mut pixels, width, vertices, texture, pixels_per_point, (clip_x.clone(), clip_y.clone()));
+        }
+    }
+
+    image::RgbaImage::from_fn(width as u32, height as u32, |x, y| {
+        let [r, g, b, a] = pixels[y as usize * width + x as usize];
+        let unpremultiply = if a > 0.0 { 1.0 / a } else { 0.0 };
+
+        image::Rgba([
+            (r * unpremultiply * 255.0).round().clamp(0.0, 255.0) as u8,
+            (g * unpremultiply * 255.0).round().clamp(0.0, 255.0) as u8,
+            (b * unpremultiply * 255.0).round().clamp(0.0, 255.0) as u8,
+            (a * 255.0).round().clamp(0.0, 255.0) as u8,
+        ])
+    })
+}
+
+fn fill_triangle(
+    pixels: &mut [[f32; 4]],
+    width: usize,
+    mut vertices: [&Vertex; 3],
+    texture: &Texture,
+    pixels_per_point: f32,
+    (clip_x, clip_y): (Range<usize>, Range<usize>),
+) {
+    let mut points = vertices.map(|vertex| (vertex.pos.x * pixels_per_point, vertex.pos.y * pixels_per_point));
+    let mut area = edge(points[0], points[1], points[2]);
+    if area.abs() < f32::EPSILON {
+        return;
+    }
+
+    // All triangles wound the same way, so neighbours walk their shared edge in opposite directions
+    if area < 0.0 {
+        vertices.swap(1, 2);
+        points.swap(1, 2);
+        area = -area;
+    }
+
+    // A pixel center right on a shared edge only goes to one of the two triangles, or it would get blended twice
+    let edges = [(points[1], points[2]), (points[2], points[0]), (points[0], points[1])];
+    let owns_edge = edges.map(|(a, b)| b.1 > a.1 || (b.1 == a.1 && b.0 < a.0));
+
+    let min_x = (points.iter().map(|point| point.0).fold(f32::INFINITY, f32::min).floor().max(0.0) as usize).max(clip_x.start);
+    let max_x = (points.iter().map(|point| point.0).fold(f32::NEG_INFINITY, f32::max).ceil().max(0.0) as usize).min(clip_x.end);
+    let min_y = (points.iter().map(|point| point.1).fold(f32::INFINITY, f32::min).floor().max(0.0) as usize).max(clip_y.start);
+    let max_y = (points.iter().map(|point| point.1).fold(f32::NEG_INFINITY, f32::max).ceil().max(0.0) as usize).min(clip_y.end);
+
+    let colors = vertices.map(|vertex| vertex.color.to_array().map(|channel| channel as f32 / 255.0));
+
+    for y in min_y..max_y {
+        for x in min_x..max_x {
+            // Barycentric weights of the pixel center
+            let center = (x as f32 + 0.5, y as f32 + 0.5);
+            let weights = edges.map(|(a, b)| edge(a, b, center) / area);
+            if weights.iter().zip(owns_edge).any(|(weight, owned)| *weight < 0.0 || (*weight == 0.0 && !owned)) {
+                continue;
+            }
+
+            let u = weights[0] * vertices[0].uv.x + weights[1] * vertices[1].uv.x + weights[2] * vertices[2].uv.x;
+            let v = weights[0] * vertices[0].uv.y + weights[1] * vertices[1].uv.y + weights[2] * vertices[2].uv.y;
+            let texel = texture.sample(u, v).to_array();
+
+            let source: [f32; 4] = std::array::from_fn(|channel| {
+                let color = weights[0] * colors[0][channel] + weights[1] * colors[1][channel] + weights[2] * colors[2][channel];
+                color * texel[channel] as f32 / 255.0
+            });
+
+            let pixel = &mut pixels[y * width + x];
+            for (target, source_channel) in pixel.iter_mut().zip(source) {
+                *target = source_channel + *target * (1.0 - source[3]);
+            }
+        }
+    }
+}
+
+fn edge(a: (f32, f32), b: (f32, f32), point: (f32, f32)) -> f32 {
+    (b.0 - a.0) * (point.1 - a.1) - (b.1 - a.1) * (point.0 - a.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use eframe::egui::{Pos2, Rect};
+    use eframe::egui::epaint::Mesh;
+
+    #[test]
+    fn rasterize_blends_each_pixel_once() {
+        let mut textures = HashMap::new();
+        textures.insert(TextureId::default(), Texture { size: [1, 1], pixels: vec![Color32::WHITE] });
+
+        let mut background = Mesh::default();
+        background.add_colored_rect(Rect::from_min_max(Pos2::ZERO, Pos2::new(4.0, 4.0)), Color32::BLACK);
+        let mut square = Mesh::default();
+        square.add_colored_rect(Rect::from_min_max(Pos2::ZERO, Pos2::new(2.0, 2.0)), Color32::from_rgba_premultiplied(128, 0, 0, 128));
+
+        let clip_rect = Rect::from_min_max(Pos2::ZERO, Pos2::new(4.0, 4.0));
+        let primitives = [background, square].map(|mesh| ClippedPrimitive { clip_rect, primitive: Primitive::Mesh(mesh) });
+
+        let image = rasterize(&primitives, &textures, [8, 8], 2.0);
+
+        assert_eq!(image.get_pixel(7, 7).0, [0, 0, 0, 255]);
+        assert_eq!(image.get_pixel(0, 0).0, [128, 0, 0, 255]);
+        // Right on the diagonal between the square's two triangles
+        assert_eq!(image.get_pixel(1, 2).0, [128, 0, 0, 255]);
+    }
+}